@@ -0,0 +1,37 @@
+//! Compares how long `Mrv` and `MrvBuckets` take to search problems with
+//! more and more primary columns. Run with
+//! `cargo bench --bench choosers`.
+
+use std::time::{Duration, Instant};
+
+use exact_cover_solver::{ColumnChooser, ExactCoverProblem, ExactCoverSolver, Mrv, MrvBuckets};
+
+/// Tilings of a ring of `width` cells by dominoes, with a column for
+/// each cell. There are none when `width` is odd, but the search only
+/// finds that out after going most of the way round the ring, with most
+/// of the columns still active at every step. Every column has two rows
+/// at the start, so the chooser sees many ties.
+fn odd_ring(width: usize) -> ExactCoverProblem {
+    let rows = (0..width).map(|i| match i + 1 == width {
+        true => vec![0, i],
+        false => vec![i, i + 1],
+    });
+    ExactCoverProblem::new(rows.map(|row| row.into_iter()), width, 0).unwrap()
+}
+
+fn time_search<C: ColumnChooser>(problem: &ExactCoverProblem, chooser: C) -> Duration {
+    let mut solver = ExactCoverSolver::with_chooser(problem, chooser);
+    let start = Instant::now();
+    assert!(solver.next_solution().is_none());
+    start.elapsed()
+}
+
+fn main() {
+    println!("{:>8} {:>12} {:>12}", "columns", "Mrv", "MrvBuckets");
+    for width in [1_001, 3_001, 10_001] {
+        let problem = odd_ring(width);
+        let mrv = time_search(&problem, Mrv);
+        let buckets = time_search(&problem, MrvBuckets::new());
+        println!("{width:>8} {mrv:>12.2?} {buckets:>12.2?}");
+    }
+}
@@ -3,10 +3,20 @@ mod solver;
 mod problems;
 
 pub use solver::{
-    ExactCoverSolver, ExactCoverProblem,
-    ExactCover, PartialCover, SolverStep,
+    ExactCoverSolver, ExactCoverProblem, ExactCoverProblemError,
+    SparseBinaryMatrix,
+    ExactCover, PartialCover, SolverStep, IndexWidth,
+    SearchBudget, SearchOutcome, SolverHandle,
     // Solutions, SolverSteps,
     ExactCoverRepresentable,
+    ColumnChooser, ColumnInfo, FirstColumn, Mrv, MrvBuckets, MrvRandomTies,
+    Priority, SolutionZdd,
+    ParallelSolver, count_solutions_parallel, SubtreeError, SubtreePath,
+    CheckpointError, Estimate, TreeSizeEstimate, Reduction, Component,
+    Decomposition, Symmetry, AssumptionError, Infeasibility,
 };
 
+#[cfg(feature = "stats")]
+pub use solver::SolverStats;
+
 pub use problems::NQueens;
@@ -1,11 +1,5 @@
-use std::time::{Duration, Instant};
-use bumpalo::Bump;
-
-use std::thread::sleep;
-use itertools::Itertools;
-
 use exact_cover_solver::{
-    ExactCoverSolver, ExactCoverRepresentable, SolverStep
+    ExactCoverSolver, ExactCoverRepresentable,
 };
 use exact_cover_solver::NQueens;
 
@@ -15,9 +9,7 @@ fn main() {
 
     let queens = Box::new(NQueens::new(n).exact_cover_problem());
     println!("Memoryreqs: {}",
-        ExactCoverSolver::memory_reqs(88, 225, 900));
-    println!("Memoryreqs!: {}",
-        ExactCoverSolver::memory_reqs(4096, 4096, 65536));
+        ExactCoverSolver::memory_reqs(&queens));
     println!(
         "rows: {}, cols: {} (pr: {} + sc: {}), ones: {}",
         queens.rows(),
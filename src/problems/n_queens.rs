@@ -4,8 +4,8 @@ use itertools::Itertools;
 
 use crate::{
     solver::{
-        ExactCover, ExactCoverSolver, ExactCoverProblem, PartialCover,
-        ExactCoverRepresentable,
+        ExactCover, ExactCoverProblem, PartialCover,
+        ExactCoverRepresentable, Symmetry,
     },
 };
 
@@ -44,18 +44,49 @@ impl ExactCoverRepresentable for NQueens {
                 });
             // These are both infallible as constructed above.
             // let matrix = SparseBinaryMatrix::from_sparse_rows(ones, num_cols).unwrap();
-            let problem = ExactCoverProblem::new(
+            ExactCoverProblem::new(
                 ones, num_cols, secondary_columns,
-            ).unwrap();
-            problem
+            ).unwrap()
         } else {
             // let matrix = SparseBinaryMatrix::from_array_2d::<0, 0>([]);
-            let problem = ExactCoverProblem::new(
-                std::iter::empty::<std::iter::Empty<_>>(), 0, 0).unwrap();
-            problem
+            ExactCoverProblem::new(
+                std::iter::empty::<std::iter::Empty<_>>(), 0, 0).unwrap()
         }
     }
 
+    /// The rotation by a quarter turn and a reflection, which generate
+    /// the eight symmetries of the board.
+    fn symmetries(&self) -> Vec<Symmetry> {
+        let n = self.0;
+        // Square (x, y) is row x*n + y. Its columns are y, then n + x,
+        // then the diagonals 2*n + x + y and 5*n - 2 + x - y.
+        let rotate_row = |r: usize| { let (x, y) = (r / n, r % n); y*n + n - 1 - x };
+        let rotate_column = |j: usize| match j {
+            j if j < n => n + j,
+            j if j < 2*n => 2*n - 1 - j,
+            j if j < 4*n - 1 => 2*n - 1 + j,
+            j => 8*n - 3 - j,
+        };
+        let reflect_row = |r: usize| { let (x, y) = (r / n, r % n); x*n + n - 1 - y };
+        let reflect_column = |j: usize| match j {
+            j if j < n => n - 1 - j,
+            j if j < 2*n => j,
+            j if j < 4*n - 1 => 2*n - 1 + j,
+            j => j + 1 - 2*n,
+        };
+        let columns = (6*n).saturating_sub(2);
+        vec![
+            Symmetry::new(
+                (0..n*n).map(rotate_row).collect(),
+                (0..columns).map(rotate_column).collect(),
+            ),
+            Symmetry::new(
+                (0..n*n).map(reflect_row).collect(),
+                (0..columns).map(reflect_column).collect(),
+            ),
+        ]
+    }
+
     fn from_exact_cover(&self, solution: &ExactCover) -> Self::TSolution {
         self.from_vec(&solution.0)
     }
@@ -78,11 +109,11 @@ impl NQueens {
     /// The `n` for this `n`-queens problem.
     pub fn n(&self) -> usize { self.0 }
 
+    #[allow(clippy::wrong_self_convention)]
     fn from_vec(&self, v: &[usize]) -> Vec<BoardSquare> {
-        let result = v.iter()
+        v.iter()
             .map(|&r| n_queens_row_to_square(r, self.0))
-            .collect::<Vec<_>>();
-        result
+            .collect::<Vec<_>>()
     }
 
     /// Relatively brute-force method to generate all solutions (generate all
@@ -134,6 +165,7 @@ fn valid_queens<'a>(queens: impl Iterator<Item = &'a BoardSquare>, n: usize) ->
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::solver::ExactCoverSolver;
 
     // Sorts all solutions individually, then sorts the list of solutions itself.
     fn sort_solutions(sol: &mut [Vec<BoardSquare>]) {
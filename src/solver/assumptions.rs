@@ -0,0 +1,91 @@
+//! Rows assumed to be in or out of every solution.
+//!
+//! Assumptions let one solver be reused for many instances of a problem
+//! that differ only in a few fixed rows, like the givens of a sudoku,
+//! without building its links again. Forbidden rows are taken out of
+//! their columns, and forced rows are pinned at the bottom of the search
+//! just like the choices along the path to a subtree.
+
+/// Something that can go wrong while setting the assumptions of a
+/// solver with
+/// [`reset_with_assumptions`](super::ExactCoverSolver::reset_with_assumptions).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssumptionError {
+    /// A row index was out of bounds.
+    RowOutOfBounds { row_idx: usize },
+    /// The row has no 1s. Empty rows never take part in the search, so
+    /// can't be forced or forbidden.
+    EmptyRow { row_idx: usize },
+    /// The row was both forced and forbidden, or clashes with an earlier
+    /// forced row.
+    Contradiction { row_idx: usize },
+    /// The problem has multiplicities, so rows can't be forced, although
+    /// they can be forbidden.
+    Multiplicities,
+    /// The problem has a symmetry that doesn't map the forced rows to
+    /// forced rows and the forbidden rows to forbidden rows, so the
+    /// solutions left can't be narrowed down to one from each class.
+    NotSymmetric,
+}
+
+/// The rows forced into and forbidden from every solution, by index,
+/// each in the order given and without repeats.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Assumptions {
+    pub(crate) forced: Vec<usize>,
+    pub(crate) forbidden: Vec<usize>,
+}
+
+impl Assumptions {
+    // Checks the given rows against the first node of each row, which is
+    // 0 for empty rows, and against the row permutations of the problem's
+    // symmetry group. Clashes between forced rows are only found once
+    // they are pinned.
+    pub(crate) fn new(
+        forced: &[usize], forbidden: &[usize], row_nodes: &[usize], multiplicities: bool,
+        symmetries: &[Vec<usize>],
+    ) -> Result<Self, AssumptionError> {
+        if !forced.is_empty() && multiplicities {
+            return Err(AssumptionError::Multiplicities);
+        }
+        // 1 for a forced row and 2 for a forbidden one.
+        let mut kind = vec![0u8; row_nodes.len()];
+        let mut assumptions = Self::default();
+        for (rows, k) in [(forced, 1), (forbidden, 2)] {
+            for &row_idx in rows {
+                match row_nodes.get(row_idx) {
+                    None => return Err(AssumptionError::RowOutOfBounds { row_idx }),
+                    Some(0) => return Err(AssumptionError::EmptyRow { row_idx }),
+                    Some(_) => (),
+                }
+                match kind[row_idx] {
+                    0 => {
+                        kind[row_idx] = k;
+                        match k {
+                            1 => assumptions.forced.push(row_idx),
+                            _ => assumptions.forbidden.push(row_idx),
+                        }
+                    },
+                    old if old != k => {
+                        return Err(AssumptionError::Contradiction { row_idx });
+                    },
+                    _ => (),
+                }
+            }
+        }
+        // As permutations, the symmetries map each set of rows onto itself
+        // if they map it into itself.
+        let invariant = symmetries.iter().all(|g| {
+            assumptions.forced.iter().all(|&r| kind[g[r]] == 1)
+                && assumptions.forbidden.iter().all(|&r| kind[g[r]] == 2)
+        });
+        if !invariant {
+            return Err(AssumptionError::NotSymmetric);
+        }
+        Ok(assumptions)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.forced.is_empty() && self.forbidden.is_empty()
+    }
+}
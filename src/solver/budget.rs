@@ -0,0 +1,91 @@
+//! Limits on the work a search may do.
+
+use std::time::Instant;
+
+/// The work a search may still do, as consumed by the
+/// [`next_solution_within`](super::ExactCoverSolver::next_solution_within)
+/// method on [`ExactCoverSolver`](super::ExactCoverSolver). Each limit
+/// that is `Some` is reduced by the work each search does, so one budget
+/// can be shared between several searches. The default budget is
+/// unlimited. Budgets are built from one limit and the `with_` methods,
+/// as more limits may be added.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SearchBudget {
+    /// The number of solver steps that may still be taken.
+    pub steps: Option<u64>,
+    /// The number of nodes of the search tree that may still be visited,
+    /// i.e. the number of rows that may still be pushed or advanced to.
+    pub nodes: Option<u64>,
+    /// The number of mems that may still be spent covering and uncovering
+    /// columns. A step may overrun this, as it is only checked between
+    /// steps. Only available with the `stats` feature, which counts mems.
+    #[cfg(feature = "stats")]
+    pub mems: Option<u64>,
+    /// The number of solutions that may still be found.
+    pub solutions: Option<u64>,
+    /// The time after which to stop. This is only checked every so many
+    /// steps, so the search may overrun it slightly.
+    pub deadline: Option<Instant>,
+}
+
+impl SearchBudget {
+    /// An unlimited budget.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// A budget of the given number of steps.
+    pub fn steps(steps: u64) -> Self {
+        Self::default().with_steps(steps)
+    }
+
+    /// A budget of the given number of nodes.
+    pub fn nodes(nodes: u64) -> Self {
+        Self::default().with_nodes(nodes)
+    }
+
+    /// A budget of the given number of mems. Only available with the
+    /// `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn mems(mems: u64) -> Self {
+        Self::default().with_mems(mems)
+    }
+
+    /// A budget of the given number of solutions.
+    pub fn solutions(solutions: u64) -> Self {
+        Self::default().with_solutions(solutions)
+    }
+
+    /// A budget running out at the given time.
+    pub fn deadline(deadline: Instant) -> Self {
+        Self::default().with_deadline(deadline)
+    }
+
+    /// The budget with its number of steps limited as well.
+    pub fn with_steps(self, steps: u64) -> Self {
+        Self { steps: Some(steps), ..self }
+    }
+
+    /// The budget with its number of nodes limited as well.
+    pub fn with_nodes(self, nodes: u64) -> Self {
+        Self { nodes: Some(nodes), ..self }
+    }
+
+    /// The budget with its number of mems limited as well. Only
+    /// available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn with_mems(self, mems: u64) -> Self {
+        Self { mems: Some(mems), ..self }
+    }
+
+    /// The budget with its number of solutions limited as well.
+    pub fn with_solutions(self, solutions: u64) -> Self {
+        Self { solutions: Some(solutions), ..self }
+    }
+
+    /// The budget with a deadline as well.
+    pub fn with_deadline(self, deadline: Instant) -> Self {
+        Self { deadline: Some(deadline), ..self }
+    }
+}
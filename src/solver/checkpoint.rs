@@ -0,0 +1,280 @@
+//! The byte format of solver checkpoints.
+//!
+//! A checkpoint starts with a magic number, a format version and the
+//! fingerprint of the problem it was taken from, followed by the solver's
+//! state as LEB128 variable-length integers, and ends with a checksum of
+//! everything before it. The state in between is laid out as the fields
+//! of a [`SavedState`] in order, with each state of the solver's stack
+//! saved as one of the [`tag`]s and a value.
+
+/// Something that can go wrong while resuming a solver from a
+/// checkpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointError {
+    /// The bytes weren't a checkpoint, or were corrupted.
+    Malformed,
+    /// The checkpoint was written in a format this version of the crate
+    /// doesn't understand.
+    UnsupportedVersion,
+    /// The checkpoint was taken from a solver for a different problem.
+    DifferentProblem,
+}
+
+const MAGIC: &[u8; 4] = b"XCck";
+const VERSION: u8 = 2;
+
+/// The tags of the states of the solver's stack, each saved with a value:
+/// the node or column named by the state, or the row it pushed.
+pub(crate) mod tag {
+    pub(crate) const START: u64 = 0;
+    pub(crate) const AFTER_COLUMN_CHOICE: u64 = 1;
+    pub(crate) const AFTER_ADD_OR_REPLACE_ROW: u64 = 2;
+    pub(crate) const AFTER_REMOVE_ROW: u64 = 3;
+    pub(crate) const RESUME: u64 = 4;
+    pub(crate) const NEXT_EMPTY_ROW_SUBSET: u64 = 5;
+    pub(crate) const OPTIONAL: u64 = 6;
+    pub(crate) const AFTER_PUSH_OPTIONAL_ROW: u64 = 7;
+    pub(crate) const RESUME_OPTIONAL: u64 = 8;
+    pub(crate) const AFTER_MULTI_COLUMN_CHOICE: u64 = 9;
+    pub(crate) const AFTER_PUSH_MULTI_ROW: u64 = 10;
+    pub(crate) const RESUME_MULTI: u64 = 11;
+    pub(crate) const RESUME_MULTI_SKIP: u64 = 12;
+    pub(crate) const AFTER_REMOVE_MULTI_ROW: u64 = 13;
+}
+
+/// The state of a solver saved in a checkpoint, in the order it is laid
+/// out. Whether it makes sense for the problem is up to the solver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SavedState {
+    pub(crate) expand_empty_rows: bool,
+    pub(crate) cost_limit: Option<u64>,
+    pub(crate) least_pruned_cost: Option<u64>,
+    pub(crate) cost: u64,
+    pub(crate) nodes_since_solution: u64,
+    /// The `(column, row)` choices along the path to the subtree.
+    pub(crate) subtree: Vec<(usize, usize)>,
+    pub(crate) forced: Vec<usize>,
+    pub(crate) forbidden: Vec<usize>,
+    pub(crate) floor: usize,
+    /// The `(tag, value)` of each state of the stack, from the bottom.
+    pub(crate) stack: Vec<(u64, usize)>,
+    pub(crate) empty_subset: Vec<usize>,
+}
+
+impl SavedState {
+    /// Writes the state as a checkpoint of the problem with the given
+    /// fingerprint.
+    pub(crate) fn write(&self, fingerprint: u64) -> Vec<u8> {
+        let mut w = CheckpointWriter::new(fingerprint);
+        w.u64(self.expand_empty_rows as u64);
+        w.option(self.cost_limit);
+        w.option(self.least_pruned_cost);
+        w.u64(self.cost);
+        w.u64(self.nodes_since_solution);
+        w.usize(self.subtree.len());
+        for &(col, row) in &self.subtree {
+            w.usize(col);
+            w.usize(row);
+        }
+        w.usizes(&self.forced);
+        w.usizes(&self.forbidden);
+        w.usize(self.floor);
+        w.usize(self.stack.len());
+        for &(tag, value) in &self.stack {
+            w.u64(tag);
+            w.usize(value);
+        }
+        w.usizes(&self.empty_subset);
+        w.finish()
+    }
+
+    /// Reads the state from a checkpoint of the problem with the given
+    /// fingerprint.
+    pub(crate) fn read(bytes: &[u8], fingerprint: u64) -> Result<Self, CheckpointError> {
+        let mut rd = CheckpointReader::new(bytes, fingerprint)?;
+        let expand_empty_rows = rd.usize_below(2)? == 1;
+        let cost_limit = rd.option()?;
+        let least_pruned_cost = rd.option()?;
+        let cost = rd.u64()?;
+        let nodes_since_solution = rd.u64()?;
+        let mut subtree = vec![];
+        for _ in 0..rd.usize()? {
+            subtree.push((rd.usize()?, rd.usize()?));
+        }
+        let forced = rd.usizes()?;
+        let forbidden = rd.usizes()?;
+        let floor = rd.usize()?;
+        let mut stack = vec![];
+        for _ in 0..rd.usize()? {
+            stack.push((rd.u64()?, rd.usize()?));
+        }
+        let empty_subset = rd.usizes()?;
+        rd.finish()?;
+        Ok(Self {
+            expand_empty_rows, cost_limit, least_pruned_cost, cost,
+            nodes_since_solution, subtree, forced, forbidden, floor, stack,
+            empty_subset,
+        })
+    }
+}
+
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+pub(crate) fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut n = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next()?;
+        let bits = u64::from(byte & 0x7f);
+        if shift >= u64::BITS || (bits << shift) >> shift != bits {
+            return None;
+        }
+        n |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+        shift += 7;
+    }
+}
+
+/// The 64-bit FNV-1a hash, fed with whole words.
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub(crate) fn write(&mut self, word: u64) {
+        self.write_bytes(&word.to_le_bytes());
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Writes a checkpoint.
+pub(crate) struct CheckpointWriter {
+    bytes: Vec<u8>,
+}
+
+impl CheckpointWriter {
+    pub(crate) fn new(fingerprint: u64) -> Self {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(fingerprint.to_le_bytes());
+        Self { bytes }
+    }
+
+    pub(crate) fn u64(&mut self, n: u64) {
+        write_varint(&mut self.bytes, n);
+    }
+
+    pub(crate) fn usize(&mut self, n: usize) {
+        self.u64(n as u64);
+    }
+
+    /// Writes a length followed by that many `usize`s.
+    pub(crate) fn usizes(&mut self, ns: &[usize]) {
+        self.usize(ns.len());
+        for &n in ns {
+            self.usize(n);
+        }
+    }
+
+    pub(crate) fn option(&mut self, n: Option<u64>) {
+        match n {
+            None => self.u64(0),
+            Some(n) => {
+                self.u64(1);
+                self.u64(n);
+            },
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        let mut hash = Fnv1a::new();
+        hash.write_bytes(&self.bytes);
+        self.bytes.extend(hash.finish().to_le_bytes());
+        self.bytes
+    }
+}
+
+/// Reads a checkpoint written by a [`CheckpointWriter`].
+pub(crate) struct CheckpointReader<'a> {
+    bytes: std::iter::Copied<std::slice::Iter<'a, u8>>,
+}
+
+impl<'a> CheckpointReader<'a> {
+    /// Checks the header and checksum of a checkpoint, ready to read the
+    /// state in between.
+    pub(crate) fn new(bytes: &'a [u8], fingerprint: u64) -> Result<Self, CheckpointError> {
+        let header = MAGIC.len() + 1 + 8;
+        if bytes.len() < header + 8 || bytes[..MAGIC.len()] != MAGIC[..] {
+            return Err(CheckpointError::Malformed);
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 8);
+        let mut hash = Fnv1a::new();
+        hash.write_bytes(body);
+        if hash.finish().to_le_bytes() != checksum {
+            return Err(CheckpointError::Malformed);
+        }
+        if body[MAGIC.len()] != VERSION {
+            return Err(CheckpointError::UnsupportedVersion);
+        }
+        let theirs = u64::from_le_bytes(body[MAGIC.len()+1..header].try_into().unwrap());
+        if theirs != fingerprint {
+            return Err(CheckpointError::DifferentProblem);
+        }
+        Ok(Self { bytes: body[header..].iter().copied() })
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, CheckpointError> {
+        read_varint(&mut self.bytes).ok_or(CheckpointError::Malformed)
+    }
+
+    /// Reads a `usize` less than `bound`.
+    pub(crate) fn usize_below(&mut self, bound: usize) -> Result<usize, CheckpointError> {
+        usize::try_from(self.u64()?).ok()
+            .filter(|&n| n < bound)
+            .ok_or(CheckpointError::Malformed)
+    }
+
+    pub(crate) fn usize(&mut self) -> Result<usize, CheckpointError> {
+        self.usize_below(usize::MAX)
+    }
+
+    /// Reads a length followed by that many `usize`s.
+    pub(crate) fn usizes(&mut self) -> Result<Vec<usize>, CheckpointError> {
+        (0..self.usize()?).map(|_| self.usize()).collect()
+    }
+
+    pub(crate) fn option(&mut self) -> Result<Option<u64>, CheckpointError> {
+        match self.u64()? {
+            0 => Ok(None),
+            1 => self.u64().map(Some),
+            _ => Err(CheckpointError::Malformed),
+        }
+    }
+
+    /// Checks that the whole state has been read.
+    pub(crate) fn finish(mut self) -> Result<(), CheckpointError> {
+        match self.bytes.next() {
+            Some(_) => Err(CheckpointError::Malformed),
+            None => Ok(()),
+        }
+    }
+}
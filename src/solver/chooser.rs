@@ -0,0 +1,280 @@
+//! Heuristics for choosing which column the solver branches on next.
+
+use super::rng::SplitMix64;
+
+/// An active primary column, as offered to a [`ColumnChooser`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnInfo {
+    /// The index of the column.
+    pub col: usize,
+    /// The number of rows that could still cover this column. For
+    /// problems with multiplicities, this is instead the number of ways
+    /// to branch on the column, as reported by `SolverStep::SelectColumn`.
+    pub size: usize,
+}
+
+/// A rule for choosing the column to branch on at each node of the
+/// search. Every choice leads to the same set of solutions, but a good
+/// rule can make the search tree dramatically smaller.
+///
+/// A chooser may also keep its own view of the columns up to date by
+/// setting `TRACKS_COLUMNS`, in which case the solver reports every
+/// change to them from within its cover and uncover operations. These
+/// calls are compiled out entirely for choosers that don't track.
+pub trait ColumnChooser {
+    /// Whether the solver should call `init`, `column_covered`,
+    /// `column_uncovered` and `size_changed`.
+    const TRACKS_COLUMNS: bool = false;
+
+    /// Chooses one of the currently active primary columns, which are
+    /// given in increasing order of index. There is always at least one.
+    fn choose(&mut self, columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo;
+
+    /// Called once when the solver is created with every active primary
+    /// column in increasing order of index.
+    fn init(&mut self, _columns: impl Iterator<Item = ColumnInfo>) {}
+
+    /// The column has been covered, i.e. removed from the active columns.
+    /// This may be a secondary column.
+    fn column_covered(&mut self, _col: usize) {}
+
+    /// The column has been uncovered with the given size, i.e. restored
+    /// to the active columns. This may be a secondary column.
+    fn column_uncovered(&mut self, _col: usize, _size: usize) {}
+
+    /// The number of rows in the column has changed to `size`. This may be
+    /// a secondary column.
+    fn size_changed(&mut self, _col: usize, _size: usize) {}
+}
+
+/// Knuth's "minimum remaining values" rule: choose the column with the
+/// fewest rows, breaking ties by lowest index. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mrv;
+
+impl ColumnChooser for Mrv {
+    fn choose(&mut self, columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo {
+        let mut best = ColumnInfo { col: usize::MAX, size: usize::MAX };
+        for c in columns {
+            if c.size < best.size {
+                best = c;
+            }
+        }
+        best
+    }
+}
+
+/// Always choose the lowest-indexed active column, regardless of size.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FirstColumn;
+
+impl ColumnChooser for FirstColumn {
+    fn choose(&mut self, mut columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo {
+        columns.next().unwrap()
+    }
+}
+
+/// Choose a column with the fewest rows, breaking ties uniformly at
+/// random. The choices are determined entirely by the seed.
+#[derive(Debug, Clone)]
+pub struct MrvRandomTies {
+    rng: SplitMix64,
+}
+
+impl MrvRandomTies {
+    /// Creates the chooser from a seed.
+    pub fn new(seed: u64) -> Self {
+        Self { rng: SplitMix64::new(seed) }
+    }
+}
+
+impl ColumnChooser for MrvRandomTies {
+    fn choose(&mut self, columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo {
+        let mut best = ColumnInfo { col: usize::MAX, size: usize::MAX };
+        let mut ties = 0;
+        for c in columns {
+            if c.size < best.size {
+                best = c;
+                ties = 1;
+            } else if c.size == best.size {
+                // Reservoir sampling over the columns of minimal size.
+                ties += 1;
+                if self.rng.below(ties) == 0 {
+                    best = c;
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Choose the column with the highest user-supplied priority, breaking
+/// ties by fewest rows and then by lowest index.
+#[derive(Debug, Clone)]
+pub struct Priority {
+    priorities: Vec<i64>,
+}
+
+impl Priority {
+    /// Creates the chooser from a priority for each primary column.
+    /// Columns beyond the end of `priorities` have priority 0.
+    pub fn new(priorities: Vec<i64>) -> Self {
+        Self { priorities }
+    }
+
+    fn priority(&self, col: usize) -> i64 {
+        self.priorities.get(col).copied().unwrap_or(0)
+    }
+}
+
+impl ColumnChooser for Priority {
+    fn choose(&mut self, columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo {
+        let mut best = ColumnInfo { col: usize::MAX, size: usize::MAX };
+        let mut best_priority = i64::MIN;
+        for c in columns {
+            let p = self.priority(c.col);
+            if p > best_priority || (p == best_priority && c.size < best.size) {
+                best = c;
+                best_priority = p;
+            }
+        }
+        best
+    }
+}
+
+/// Chooses exactly the same columns as [`Mrv`], but without walking the
+/// whole list of active columns at every step.
+///
+/// The active primary columns are kept in buckets keyed by size. Each
+/// bucket is a bitset of its columns together with a summary bit for
+/// each of its nonzero words, so moving a column between buckets as its
+/// size changes takes constant time, and the lowest index among the
+/// columns tied for the least size is found by looking at one summary
+/// word per 4096 columns and one word of the bitset. This pays off on
+/// problems with many primary columns, where `Mrv` spends most of its
+/// time scanning columns that are far from minimal. The `choosers` bench
+/// compares the two.
+///
+/// A bucket is only allocated once some column has its size, and takes
+/// a bit per primary column.
+#[derive(Debug, Clone, Default)]
+pub struct MrvBuckets {
+    // The primary columns of each size.
+    buckets: Vec<ColumnSet>,
+    // The current size of each primary column.
+    sizes: Vec<usize>,
+    // No nonempty bucket is smaller than this.
+    min: usize,
+}
+
+/// A set of column indices below a fixed bound, as a bitset with a
+/// summary bit for each nonzero word.
+#[derive(Debug, Clone, Default)]
+struct ColumnSet {
+    words: Vec<u64>,
+    summary: Vec<u64>,
+    len: usize,
+}
+
+impl ColumnSet {
+    fn new(num_cols: usize) -> Self {
+        let words = num_cols.div_ceil(64);
+        Self { words: vec![0; words], summary: vec![0; words.div_ceil(64)], len: 0 }
+    }
+
+    fn insert(&mut self, col: usize) {
+        let w = col / 64;
+        if self.words[w] == 0 {
+            self.summary[w / 64] |= 1 << (w % 64);
+        }
+        self.words[w] |= 1 << (col % 64);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, col: usize) {
+        let w = col / 64;
+        self.words[w] &= !(1 << (col % 64));
+        if self.words[w] == 0 {
+            self.summary[w / 64] &= !(1 << (w % 64));
+        }
+        self.len -= 1;
+    }
+
+    // The least column in the set, which must be nonempty.
+    fn first(&self) -> usize {
+        let (i, s) = self.summary.iter().enumerate()
+            .find(|&(_, &s)| s != 0)
+            .expect("the set is nonempty");
+        let w = i*64 + s.trailing_zeros() as usize;
+        w*64 + self.words[w].trailing_zeros() as usize
+    }
+}
+
+impl MrvBuckets {
+    /// Creates the chooser. Its buckets are set up when it's handed to
+    /// a solver.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn is_primary(&self, col: usize) -> bool {
+        col < self.sizes.len()
+    }
+
+    fn insert(&mut self, col: usize, size: usize) {
+        if size >= self.buckets.len() {
+            self.buckets.resize_with(size+1, ColumnSet::default);
+        }
+        let bucket = &mut self.buckets[size];
+        if bucket.words.is_empty() {
+            *bucket = ColumnSet::new(self.sizes.len());
+        }
+        bucket.insert(col);
+        self.sizes[col] = size;
+        self.min = self.min.min(size);
+    }
+
+    fn remove(&mut self, col: usize) {
+        self.buckets[self.sizes[col]].remove(col);
+    }
+}
+
+impl ColumnChooser for MrvBuckets {
+    const TRACKS_COLUMNS: bool = true;
+
+    fn choose(&mut self, _columns: impl Iterator<Item = ColumnInfo>) -> ColumnInfo {
+        while self.buckets[self.min].len == 0 {
+            self.min += 1;
+        }
+        // Ties are broken by lowest index, like Mrv.
+        ColumnInfo { col: self.buckets[self.min].first(), size: self.min }
+    }
+
+    fn init(&mut self, columns: impl Iterator<Item = ColumnInfo>) {
+        *self = Self::default();
+        let columns = columns.collect::<Vec<_>>();
+        self.sizes = vec![0; columns.len()];
+        for c in columns {
+            self.insert(c.col, c.size);
+        }
+    }
+
+    fn column_covered(&mut self, col: usize) {
+        if self.is_primary(col) {
+            self.remove(col);
+        }
+    }
+
+    fn column_uncovered(&mut self, col: usize, size: usize) {
+        if self.is_primary(col) {
+            self.insert(col, size);
+        }
+    }
+
+    fn size_changed(&mut self, col: usize, size: usize) {
+        if self.is_primary(col) {
+            self.remove(col);
+            self.insert(col, size);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Splitting a problem into independent parts.
+//!
+//! Two columns are connected if some row has 1s in both. Each connected
+//! set of columns, with the rows having 1s in them, makes up a component
+//! whose solutions can be found without regard to the others, and every
+//! solution of the whole problem is one solution of each component put
+//! together. The empty rows make up a component of their own.
+
+use super::{ExactCover, ExactCoverProblem, ExactCoverSolver};
+
+/// A problem split into independent components, from
+/// [`ExactCoverProblem::decompose`].
+pub struct Decomposition {
+    components: Vec<Component>,
+}
+
+/// One independent part of a problem, as a problem in its own right.
+pub struct Component {
+    problem: ExactCoverProblem,
+    // The original index of each row and column of the component.
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+}
+
+impl Component {
+    /// The component as a problem of its own, with its rows and columns
+    /// in the same order as in the original problem.
+    pub fn problem(&self) -> &ExactCoverProblem {
+        &self.problem
+    }
+
+    /// The index in the original problem of a row of the component.
+    pub fn original_row(&self, row: usize) -> usize {
+        self.rows[row]
+    }
+
+    /// The index in the original problem of a column of the component.
+    pub fn original_column(&self, col: usize) -> usize {
+        self.columns[col]
+    }
+
+    // Every solution of the component, in terms of the original rows.
+    fn solutions(&self) -> Vec<Vec<usize>> {
+        ExactCoverSolver::new(&self.problem)
+            .iter_solutions()
+            .map(|s| s.0.into_iter().map(|r| self.rows[r]).collect())
+            .collect()
+    }
+
+    // The number of solutions of the component, or `None` if it doesn't
+    // fit in a `u128`.
+    fn count_solutions(&self) -> Option<u128> {
+        let mut solver = ExactCoverSolver::new(&self.problem);
+        let multiplier = solver.empty_row_multiplier()?;
+        solver.set_expand_empty_rows(false);
+        solver.count_solutions().checked_mul(multiplier)
+    }
+}
+
+impl Decomposition {
+    /// The components, ordered by their first column. The empty rows, if
+    /// any, come last as a component without columns.
+    pub fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    /// Counts the solutions of the whole problem by counting those of
+    /// each component and multiplying, or returns `None` if the count
+    /// doesn't fit in a `u128`.
+    pub fn count_solutions(&self) -> Option<u128> {
+        let mut total = 1u128;
+        for component in &self.components {
+            let count = component.count_solutions()?;
+            if count == 0 {
+                return Some(0);
+            }
+            total = total.checked_mul(count)?;
+        }
+        Some(total)
+    }
+
+    /// Solves each component and returns an iterator through every
+    /// combination of their solutions, each a solution of the whole
+    /// problem with its rows in increasing order. The solutions of every
+    /// component are kept in memory, but not their combinations.
+    pub fn iter_solutions(&self) -> ComponentSolutions {
+        let mut solutions = Vec::with_capacity(self.components.len());
+        for component in &self.components {
+            let s = component.solutions();
+            if s.is_empty() {
+                return ComponentSolutions { solutions: vec![], next: None };
+            }
+            solutions.push(s);
+        }
+        let next = Some(vec![0; solutions.len()]);
+        ComponentSolutions { solutions, next }
+    }
+}
+
+/// An iterator through the solutions of a problem as combinations of the
+/// solutions of its components. This `struct` is created by the
+/// [`iter_solutions`](Decomposition::iter_solutions) method on
+/// [`Decomposition`].
+#[derive(Debug)]
+pub struct ComponentSolutions {
+    solutions: Vec<Vec<Vec<usize>>>,
+    // The index of the next solution of each component to combine, or
+    // `None` once every combination has been produced.
+    next: Option<Vec<usize>>,
+}
+
+impl Iterator for ComponentSolutions {
+    type Item = ExactCover;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let indices = self.next.as_mut()?;
+        let mut rows = indices.iter()
+            .zip(&self.solutions)
+            .flat_map(|(&i, s)| s[i].iter().copied())
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+
+        // Advances the last component fastest, like an odometer.
+        let mut k = indices.len();
+        loop {
+            if k == 0 {
+                self.next = None;
+                break;
+            }
+            k -= 1;
+            indices[k] += 1;
+            if indices[k] < self.solutions[k].len() {
+                break;
+            }
+            indices[k] = 0;
+        }
+        Some(ExactCover(rows))
+    }
+}
+
+pub(crate) fn decompose(problem: &ExactCoverProblem) -> Decomposition {
+    let rows = problem.ordered_coloured_rows()
+        .map(|row| row.collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // Union-find over the columns, joining the columns of each row.
+    let mut parent = (0..problem.columns()).collect::<Vec<_>>();
+    fn find(parent: &mut [usize], mut j: usize) -> usize {
+        while parent[j] != j {
+            parent[j] = parent[parent[j]];
+            j = parent[j];
+        }
+        j
+    }
+    for row in &rows {
+        if let Some(&(first, _)) = row.first() {
+            for &(j, _) in &row[1..] {
+                let (a, b) = (find(&mut parent, first), find(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    // Each component is named by its first column. Secondary columns
+    // without rows constrain nothing and are left out.
+    let mut col_rows = vec![0; problem.columns()];
+    for &(j, _) in rows.iter().flatten() {
+        col_rows[j] += 1;
+    }
+    let mut members = vec![(vec![], vec![]); problem.columns()];
+    let mut empty_rows = vec![];
+    for (j, &count) in col_rows.iter().enumerate() {
+        if j < problem.primary_columns() || count > 0 {
+            let root = find(&mut parent, j);
+            members[root].0.push(j);
+        }
+    }
+    for (r, row) in rows.iter().enumerate() {
+        match row.first() {
+            Some(&(j, _)) => {
+                let root = find(&mut parent, j);
+                members[root].1.push(r);
+            },
+            None => empty_rows.push(r),
+        }
+    }
+    let mut components = members.into_iter()
+        .filter(|(columns, _)| !columns.is_empty())
+        .map(|(columns, members)| component(problem, &rows, columns, members))
+        .collect::<Vec<_>>();
+    if !empty_rows.is_empty() {
+        components.push(component(problem, &rows, vec![], empty_rows));
+    }
+    Decomposition { components }
+}
+
+// The component of a problem made up of the given rows and columns, in
+// increasing order, given the entries of every row of the problem.
+fn component(
+    problem: &ExactCoverProblem, entries: &[Vec<(usize, usize)>],
+    columns: Vec<usize>, rows: Vec<usize>,
+) -> Component {
+    let mut new_index = vec![usize::MAX; problem.columns()];
+    for (i, &j) in columns.iter().enumerate() {
+        new_index[j] = i;
+    }
+    let primary = columns.iter()
+        .filter(|&&j| j < problem.primary_columns())
+        .count();
+    let mut sub = ExactCoverProblem::new_coloured(
+        rows.iter().map(|&r| entries[r].iter()
+            .map(|&(j, colour)| (new_index[j], colour))),
+        columns.len(),
+        columns.len() - primary,
+    ).expect("component is well formed");
+    if problem.has_costs() {
+        let costs = rows.iter().map(|&r| problem.cost(r)).collect();
+        sub.set_costs(costs).expect("one cost per row");
+    }
+    if problem.has_multiplicities() {
+        let multiplicities = columns[..primary].iter()
+            .map(|&j| problem.multiplicity(j))
+            .collect();
+        sub.set_multiplicities(multiplicities).expect("multiplicities were valid");
+    }
+    Component { problem: sub, rows, columns }
+}
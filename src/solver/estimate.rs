@@ -0,0 +1,72 @@
+//! Monte Carlo estimates of the size of a search.
+//!
+//! Following Knuth, a random path is followed from the root of the search
+//! tree to a leaf, choosing uniformly among the branches at each level.
+//! If the levels along the path have `d1, d2, ...` branches, then the
+//! `n`th level has about `d1 * d2 * ... * dn` nodes, and summing these
+//! gives an unbiased estimate of the size of the tree. Averaging over
+//! many paths tames the estimate's often enormous variance.
+
+/// An estimate of a quantity from a number of random samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    /// The mean of the samples, which is an unbiased estimate of the
+    /// quantity.
+    pub mean: f64,
+    /// The variance of the samples.
+    pub variance: f64,
+    /// The number of samples.
+    pub samples: u64,
+}
+
+impl Estimate {
+    /// The standard error of the mean, i.e. the standard deviation of the
+    /// estimate itself.
+    pub fn standard_error(&self) -> f64 {
+        (self.variance / self.samples as f64).sqrt()
+    }
+}
+
+/// Estimates of the size of a search, from the
+/// [`estimate_tree_size`](super::ExactCoverSolver::estimate_tree_size)
+/// method on [`ExactCoverSolver`](super::ExactCoverSolver).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeSizeEstimate {
+    /// The number of nodes in the search tree, counting the root and one
+    /// node per row pushed or advanced to.
+    pub nodes: Estimate,
+    /// The number of solutions the search reports.
+    pub solutions: Estimate,
+    /// The number of mems the search spends, as counted by
+    /// [`mems`](super::ExactCoverSolver::mems). Only available with the
+    /// `stats` feature.
+    #[cfg(feature = "stats")]
+    pub mems: Estimate,
+}
+
+/// Accumulates samples with Welford's method, which keeps the variance
+/// accurate when it is tiny compared to the mean.
+#[derive(Debug, Default)]
+pub(crate) struct Samples {
+    n: u64,
+    mean: f64,
+    // The sum of squared differences from the mean.
+    m2: f64,
+}
+
+impl Samples {
+    pub(crate) fn add(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    pub(crate) fn estimate(&self) -> Estimate {
+        let variance = match self.n {
+            0 | 1 => 0.0,
+            n => self.m2 / (n - 1) as f64,
+        };
+        Estimate { mean: self.mean, variance, samples: self.n }
+    }
+}
@@ -0,0 +1,102 @@
+//! Watching and cancelling a search from another thread.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// The progress of a search, written by the solver and read by any
+/// number of handles. The solver is the only writer of everything but
+/// `cancelled`, so plain loads and stores suffice.
+#[derive(Debug, Default)]
+pub(crate) struct Progress {
+    cancelled: AtomicBool,
+    solutions: AtomicU64,
+    steps: AtomicU64,
+    depth: AtomicUsize,
+    // The bits of an f64.
+    fraction: AtomicU64,
+}
+
+impl Progress {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_step(&self, solution: bool, depth: usize) {
+        let steps = self.steps.load(Ordering::Relaxed);
+        self.steps.store(steps + 1, Ordering::Relaxed);
+        if solution {
+            let solutions = self.solutions.load(Ordering::Relaxed);
+            self.solutions.store(solutions + 1, Ordering::Relaxed);
+        }
+        self.depth.store(depth, Ordering::Relaxed);
+    }
+
+    pub(crate) fn steps(&self) -> u64 {
+        self.steps.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_fraction(&self, fraction: f64) {
+        self.fraction.store(fraction.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// The solver's end of its handles, which a clone of the solver doesn't
+/// share: a fork starts out with no handles of its own.
+#[derive(Debug, Default)]
+pub(crate) struct ProgressLink(pub(crate) Option<Arc<Progress>>);
+
+impl Clone for ProgressLink {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+/// A handle on a running search, through which another thread can watch
+/// its progress and cancel it without locking. This `struct` is created
+/// by the [`handle`](super::ExactCoverSolver::handle) method on
+/// [`ExactCoverSolver`](super::ExactCoverSolver). Handles are cheap to
+/// clone, and all handles on a solver share the same state.
+///
+/// Progress is counted from when the first handle was made.
+#[derive(Debug, Clone)]
+pub struct SolverHandle {
+    pub(crate) progress: Arc<Progress>,
+}
+
+impl SolverHandle {
+    /// Asks the search to stop. It stops before its next step, after
+    /// which the solver behaves as though there are no more steps.
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the search has been asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.is_cancelled()
+    }
+
+    /// The number of solutions found.
+    pub fn solutions(&self) -> u64 {
+        self.progress.solutions.load(Ordering::Relaxed)
+    }
+
+    /// The number of steps taken.
+    pub fn steps(&self) -> u64 {
+        self.progress.steps()
+    }
+
+    /// The depth of the search's current position in its tree, i.e. the
+    /// number of levels of choices it is in the middle of.
+    pub fn depth(&self) -> usize {
+        self.progress.depth.load(Ordering::Relaxed)
+    }
+
+    /// An estimate of the fraction of the search tree explored so far,
+    /// between 0 and 1, which is exactly 1 once the search is finished.
+    /// Each branch at each level is taken to be equally big, so this
+    /// tends to jump about on unbalanced trees. It is only updated every
+    /// so many steps.
+    pub fn fraction_explored(&self) -> f64 {
+        f64::from_bits(self.progress.fraction.load(Ordering::Relaxed))
+    }
+}
@@ -0,0 +1,152 @@
+//! Explaining why a problem has no solutions.
+//!
+//! Restricting a problem to some of its columns, keeping just the rows
+//! with a 1 in one of them, can only make it easier to solve: the rows of
+//! any solution that meet those columns solve the restriction. So a set
+//! of columns whose restriction has no solutions shows that the whole
+//! problem has none, and the fewer the columns, the clearer the reason.
+
+use super::{ExactCoverProblem, ExactCoverSolver};
+
+/// The reasons a problem has no solutions, from
+/// [`ExactCoverProblem::explain_infeasibility`]. Rows and columns are
+/// given by their indices in the problem, in increasing order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Infeasibility {
+    empty_columns: Vec<usize>,
+    forced_rows: Vec<usize>,
+    uncoverable_columns: Vec<usize>,
+    core_columns: Vec<usize>,
+    core_rows: Vec<usize>,
+}
+
+impl Infeasibility {
+    /// The primary columns with too few rows to cover them as many times
+    /// as they must be, which for an exact cover means no rows at all.
+    pub fn empty_columns(&self) -> &[usize] {
+        &self.empty_columns
+    }
+
+    /// The rows forced into every solution by being the only row left in
+    /// some primary column, as found by [`ExactCoverProblem::reduce`].
+    pub fn forced_rows(&self) -> &[usize] {
+        &self.forced_rows
+    }
+
+    /// The primary columns with rows of their own, but none left once
+    /// the forced rows are chosen and the rows that can never be chosen
+    /// are taken out.
+    pub fn uncoverable_columns(&self) -> &[usize] {
+        &self.uncoverable_columns
+    }
+
+    /// A set of columns that can't be covered using the rows with a 1 in
+    /// them, and which is minimal: without any one of them, the rest
+    /// could be.
+    pub fn core_columns(&self) -> &[usize] {
+        &self.core_columns
+    }
+
+    /// The rows with a 1 in one of the core columns.
+    pub fn core_rows(&self) -> &[usize] {
+        &self.core_rows
+    }
+}
+
+pub(crate) fn explain_infeasibility(problem: &ExactCoverProblem) -> Option<Infeasibility> {
+    if has_solution(problem) {
+        return None;
+    }
+    let rows = problem.ordered_coloured_rows()
+        .map(|row| row.collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut col_rows = vec![0; problem.columns()];
+    for &(j, _) in rows.iter().flatten() {
+        col_rows[j] += 1;
+    }
+    let empty_columns = (0..problem.primary_columns())
+        .filter(|&c| problem.multiplicity(c).0 > col_rows[c])
+        .collect::<Vec<_>>();
+
+    let (reduced, reduction) = problem.reduce();
+    let mut reduced_rows = vec![0; reduced.columns()];
+    for j in reduced.ordered_points_rows().flatten() {
+        reduced_rows[j] += 1;
+    }
+    let mut uncoverable_columns = (0..reduced.primary_columns())
+        .filter(|&c| reduced_rows[c] == 0)
+        .map(|c| reduction.original_column(c))
+        .filter(|&c| col_rows[c] > 0)
+        .collect::<Vec<_>>();
+    uncoverable_columns.sort_unstable();
+
+    // Takes out one column at a time, keeping it out if what is left
+    // still has no solutions. An empty column is a core on its own, and
+    // the secondary columns are often not needed at all.
+    let mut core_columns = match empty_columns.first() {
+        Some(&c) => vec![c],
+        None => {
+            let all = (0..problem.columns()).collect::<Vec<_>>();
+            let primary = all[..problem.primary_columns()].to_vec();
+            match has_solution(&restrict(problem, &rows, &primary)) {
+                true => all,
+                false => primary,
+            }
+        },
+    };
+    let mut i = 0;
+    while i < core_columns.len() && core_columns.len() > 1 {
+        let mut fewer = core_columns.clone();
+        fewer.remove(i);
+        if has_solution(&restrict(problem, &rows, &fewer)) {
+            i += 1;
+        } else {
+            core_columns = fewer;
+        }
+    }
+    let core_rows = (0..rows.len())
+        .filter(|&r| rows[r].iter().any(|(j, _)| core_columns.binary_search(j).is_ok()))
+        .collect();
+
+    Some(Infeasibility {
+        empty_columns,
+        forced_rows: reduction.forced_rows().to_vec(),
+        uncoverable_columns,
+        core_columns,
+        core_rows,
+    })
+}
+
+fn has_solution(problem: &ExactCoverProblem) -> bool {
+    ExactCoverSolver::new(problem).next_solution().is_some()
+}
+
+// The problem restricted to the given columns, in increasing order, and
+// the rows with a 1 in one of them, given the entries of every row.
+fn restrict(
+    problem: &ExactCoverProblem, entries: &[Vec<(usize, usize)>], columns: &[usize],
+) -> ExactCoverProblem {
+    let mut new_index = vec![usize::MAX; problem.columns()];
+    for (i, &j) in columns.iter().enumerate() {
+        new_index[j] = i;
+    }
+    let primary = columns.iter()
+        .filter(|&&j| j < problem.primary_columns())
+        .count();
+    let rows = entries.iter()
+        .map(|row| row.iter()
+            .filter(|&&(j, _)| new_index[j] != usize::MAX)
+            .map(|&(j, colour)| (new_index[j], colour))
+            .collect::<Vec<_>>())
+        .filter(|row| !row.is_empty());
+    let mut restricted = ExactCoverProblem::new_coloured(
+        rows.map(|row| row.into_iter()), columns.len(), columns.len() - primary,
+    ).expect("restriction is well formed");
+    if problem.has_multiplicities() {
+        let multiplicities = columns[..primary].iter()
+            .map(|&j| problem.multiplicity(j))
+            .collect();
+        restricted.set_multiplicities(multiplicities).expect("multiplicities were valid");
+    }
+    restricted
+}
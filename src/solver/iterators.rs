@@ -1,16 +1,17 @@
 use super::solver::ExactCoverSolver;
 use super::output::{ExactCover, SolverStep};
+use super::chooser::{ColumnChooser, Mrv};
 
 /// An iterator over the remaining solutions from an
 /// [`ExactCoverSolver`]. This `struct` is created by the
 /// [`iter_solutions`](ExactCoverSolver::iter_solutions)
 /// method on [`ExactCoverSolver`]. See its documentation for more
 /// information.
-pub struct Solutions<'a> {
-    pub(super) solver: &'a mut ExactCoverSolver,
+pub struct Solutions<'a, C = Mrv> {
+    pub(super) solver: &'a mut ExactCoverSolver<C>,
 }
 
-impl<'a> Iterator for Solutions<'a> {
+impl<'a, C: ColumnChooser> Iterator for Solutions<'a, C> {
     type Item = ExactCover;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -18,16 +19,54 @@ impl<'a> Iterator for Solutions<'a> {
     }
 }
 
+/// An iterator over all solutions from an [`ExactCoverSolver`] in
+/// order of increasing cost, each with its cost. This `struct` is created
+/// by the [`iter_solutions_by_cost`](ExactCoverSolver::iter_solutions_by_cost)
+/// method on [`ExactCoverSolver`]. See its documentation for more
+/// information.
+pub struct SolutionsByCost<'a, C = Mrv> {
+    pub(super) solver: &'a mut ExactCoverSolver<C>,
+    // The current band of solutions, most expensive first.
+    pub(super) band: Vec<(ExactCover, u64)>,
+    // The cost limit of the next round, if there is one.
+    pub(super) next_limit: Option<u64>,
+    // Every solution costing at most this has been found.
+    pub(super) found_up_to: Option<u64>,
+}
+
+impl<'a, C: ColumnChooser> Iterator for SolutionsByCost<'a, C> {
+    type Item = (ExactCover, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(solution) = self.band.pop() {
+                return Some(solution);
+            }
+            let limit = self.next_limit?;
+            let (mut band, least_pruned) = self.solver
+                .solutions_in_cost_band(self.found_up_to, limit);
+            // Popping from the end must give the cheapest solution first,
+            // and the earliest found of those of equal cost.
+            band.reverse();
+            band.sort_by_key(|&(_, cost)| std::cmp::Reverse(cost));
+            self.band = band;
+            self.found_up_to = Some(limit);
+            // No solution costs less than the least pruned cost.
+            self.next_limit = least_pruned;
+        }
+    }
+}
+
 /// An iterator over the remaining solver steps from an
 /// [`ExactCoverSolver`]. This `struct` is created by the
 /// [`iter_steps`](ExactCoverSolver::iter_steps)
 /// method on [`ExactCoverSolver`]. See its documentation for more
 /// information.
-pub struct SolverSteps<'a> {
-    pub(super) solver: &'a mut ExactCoverSolver,
+pub struct SolverSteps<'a, C = Mrv> {
+    pub(super) solver: &'a mut ExactCoverSolver<C>,
 }
 
-impl<'a> Iterator for SolverSteps<'a> {
+impl<'a, C: ColumnChooser> Iterator for SolverSteps<'a, C> {
     type Item = SolverStep;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -0,0 +1,1051 @@
+//! The dancing links data structure underlying the solver.
+//!
+//! The layout follows Knuth's DLX programs rather than the torus of
+//! seven-field nodes from the original paper. There are two arrays:
+//!
+//! - `headers`, holding the left and right links of the column lists.
+//!   Index 0 is the root of the list of active primary columns, indices
+//!   1 to N are the columns themselves, and index N+1 is the root of the
+//!   list of active secondary columns.
+//! - `nodes`, holding a `top`, `up` and `down` field for every other
+//!   node. Index 0 is unused; indices 1 to N are the column headers, whose
+//!   `top` is the number of active rows in the column. After those come
+//!   the rows, stored contiguously with a spacer node before the first
+//!   row and after every row.
+//!
+//! For an ordinary node `top` is its column. A spacer is recognised by
+//! having `top` greater than N; its `up` points at the first node of the
+//! row before it, its `down` at the last node of the row after it, and
+//! its `top` is N+1 plus the label of the row before it. Moving left and
+//! right along a row is then a matter of stepping through the array and
+//! jumping back through the spacer at either end, so no node needs left
+//! or right links, a column or a row label of its own.
+//!
+//! For problems with colours there are two more arrays: the colour of
+//! every node (0 for uncoloured nodes, headers and spacers), and for
+//! every column the node that purified it, if any. Following Knuth's
+//! Algorithm C, choosing a row with a node of colour c in a secondary
+//! column "purifies" that column, hiding every row that gives it a
+//! different colour, instead of covering it. Unlike Knuth we don't mark
+//! the surviving nodes, so colours never change; a node is in agreement
+//! with a purified column exactly when its colour matches the purifying
+//! node's.
+//!
+//! For problems with multiplicities, every column also has a `bound`, the
+//! number of times it may still be covered, and a fixed `slack`, the
+//! difference between its upper and lower bounds, as in Knuth's Algorithm
+//! M. Branching on such a column removes ("tweaks") its rows from it one
+//! at a time as each is tried, so that the rows chosen for it at deeper
+//! levels come in increasing order and no combination is tried twice.
+//!
+//! All of this is generic over the width of an index, and the solver
+//! picks the narrowest of `u16`, `u32` and `usize` that fits the problem.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use super::{ColumnChooser, ColumnInfo, ExactCoverProblem};
+use super::rng::SplitMix64;
+
+/// An unsigned integer type that can index the nodes of a problem.
+pub(crate) trait Index: Copy + Eq + Ord + Debug {
+    fn from_usize(i: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+macro_rules! impl_index {
+    ($($t:ty),*) => {$(
+        impl Index for $t {
+            #[inline(always)]
+            fn from_usize(i: usize) -> Self { i as $t }
+            #[inline(always)]
+            fn to_usize(self) -> usize { self as usize }
+        }
+    )*};
+}
+
+impl_index!(u16, u32, usize);
+
+#[derive(Debug, Clone, Copy)]
+struct Header<I> {
+    left: I,
+    right: I,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node<I> {
+    top: I,
+    up: I,
+    down: I,
+}
+
+/// The dancing links for a problem with a given index width.
+#[derive(Debug, Clone)]
+pub(crate) struct Dlx<I> {
+    // The number of columns N.
+    n: usize,
+    headers: Vec<Header<I>>,
+    nodes: Vec<Node<I>>,
+    // The colour of each node, or empty if the problem is uncoloured.
+    // Colours never change, so they are shared between copies.
+    colours: Arc<[I]>,
+    // The node that purified each column, or 0 if it isn't purified.
+    // Empty if the problem is uncoloured.
+    purified_by: Vec<I>,
+    // The number of primary columns.
+    primary: usize,
+    // The bound and slack of each column, or empty if every primary
+    // column must be covered exactly once.
+    bounds: Vec<I>,
+    slacks: Vec<I>,
+    // Roughly the number of memory accesses made so far, following
+    // Knuth: a mem for each node or header read or written while
+    // covering and uncovering. Only counted with the `stats` feature.
+    #[cfg(feature = "stats")]
+    mems: u64,
+}
+
+/// What to do next when branching on a column with multiplicities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Branch {
+    /// Try the row containing this node.
+    Row(usize),
+    /// Try covering the column no more times.
+    Skip,
+    /// Nothing left to try.
+    Exhausted,
+}
+
+impl<I: Index> Dlx<I> {
+    fn new(problem: &ExactCoverProblem, nonempty_rows: usize) -> Self {
+        let primary = problem.primary_columns();
+        let n = problem.columns();
+        let num_nodes = n + 1 + problem.num_ones() + nonempty_rows + 1;
+        let idx = I::from_usize;
+
+        // The primary list runs 0, 1, ..., primary and the secondary
+        // list runs N+1, primary+1, ..., N.
+        let mut headers = Vec::with_capacity(n + 2);
+        for c in 0..n+2 {
+            let (left, right) = if c <= primary {
+                (if c == 0 { primary } else { c-1 },
+                    if c == primary { 0 } else { c+1 })
+            } else {
+                (if c == primary+1 { n+1 } else { c-1 },
+                    if c == n+1 { primary+1 } else { c+1 })
+            };
+            headers.push(Header { left: idx(left), right: idx(right) });
+        }
+
+        let mut nodes = Vec::with_capacity(num_nodes);
+        for c in 0..n+1 {
+            nodes.push(Node { top: idx(0), up: idx(c), down: idx(c) });
+        }
+
+        // Colours are renumbered 1, 2, ... in order of appearance so that
+        // they fit in an index.
+        let coloured = problem.is_coloured();
+        let mut colours = vec![];
+        let mut colour_ids = HashMap::new();
+
+        // The first spacer. Its up link is never followed.
+        let mut spacer = nodes.len();
+        nodes.push(Node { top: idx(n+1), up: idx(0), down: idx(0) });
+        for (label, row) in problem.ordered_coloured_rows().enumerate() {
+            let first = nodes.len();
+            for (j, colour) in row {
+                if coloured {
+                    colours.resize(nodes.len(), idx(0));
+                    let next_id = colour_ids.len() + 1;
+                    let id = match colour {
+                        0 => 0,
+                        _ => *colour_ids.entry(colour).or_insert(next_id),
+                    };
+                    colours.push(idx(id));
+                }
+                let c = j + 1;
+                let x = nodes.len();
+                let last = nodes[c].up.to_usize();
+                nodes.push(Node { top: idx(c), up: idx(last), down: idx(c) });
+                nodes[last].down = idx(x);
+                nodes[c].up = idx(x);
+                nodes[c].top = idx(nodes[c].top.to_usize() + 1);
+            }
+            if nodes.len() > first {
+                nodes[spacer].down = idx(nodes.len() - 1);
+                spacer = nodes.len();
+                nodes.push(Node {
+                    top: idx(n + 1 + label), up: idx(first), down: idx(0),
+                });
+            }
+        }
+
+        let purified_by = if coloured {
+            colours.resize(nodes.len(), idx(0));
+            vec![idx(0); n+1]
+        } else {
+            vec![]
+        };
+        // Bounds beyond the length of a column make no difference, and
+        // capping them ensures they fit in an index.
+        let (mut bounds, mut slacks) = (vec![], vec![]);
+        if problem.has_multiplicities() {
+            bounds.reserve_exact(n+1);
+            slacks.reserve_exact(n+1);
+            for (c, header) in nodes[..n+1].iter().enumerate() {
+                let (lo, hi) = match c {
+                    0 => (1, 1),
+                    _ => problem.multiplicity(c-1),
+                };
+                let cap = header.top.to_usize() + 1;
+                let (lo, hi) = (lo.min(cap), hi.min(cap));
+                bounds.push(idx(hi));
+                slacks.push(idx(hi - lo));
+            }
+        }
+        Self {
+            n, headers, nodes, colours: colours.into(), purified_by,
+            primary, bounds, slacks,
+            #[cfg(feature = "stats")]
+            mems: 0,
+        }
+    }
+
+    #[inline(always)]
+    fn top(&self, x: usize) -> usize { self.nodes[x].top.to_usize() }
+    #[inline(always)]
+    fn up(&self, x: usize) -> usize { self.nodes[x].up.to_usize() }
+    #[inline(always)]
+    fn down(&self, x: usize) -> usize { self.nodes[x].down.to_usize() }
+    #[inline(always)]
+    fn left(&self, c: usize) -> usize { self.headers[c].left.to_usize() }
+    #[inline(always)]
+    fn right(&self, c: usize) -> usize { self.headers[c].right.to_usize() }
+
+    // The label of the row containing node x.
+    fn row_label(&self, mut x: usize) -> usize {
+        while self.top(x) <= self.n {
+            x += 1;
+        }
+        self.top(x) - self.n - 1
+    }
+
+    // Adds to the mems spent. Without the `stats` feature this does
+    // nothing, and the mems counted by `hide` and `unhide` are dropped.
+    #[inline(always)]
+    fn count_mems(&mut self, mems: u64) {
+        #[cfg(feature = "stats")]
+        {
+            self.mems += mems;
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = mems;
+    }
+
+    // Removes the other nodes of p's row from their columns, returning
+    // the number of mems this took.
+    fn hide<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) -> u64 {
+        let mut mems = 0;
+        let mut q = p + 1;
+        while q != p {
+            let x = self.top(q);
+            let u = self.up(q);
+            if x > self.n {
+                q = u;
+                mems += 1;
+            } else {
+                let d = self.down(q);
+                self.nodes[u].down = I::from_usize(d);
+                self.nodes[d].up = I::from_usize(u);
+                let size = self.top(x) - 1;
+                self.nodes[x].top = I::from_usize(size);
+                if C::TRACKS_COLUMNS {
+                    chooser.size_changed(x-1, self.branching(x));
+                }
+                q += 1;
+                mems += 4;
+            }
+        }
+        mems
+    }
+
+    // Undoes `hide(p)`, returning the number of mems this took.
+    fn unhide<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) -> u64 {
+        let mut mems = 0;
+        let mut q = p - 1;
+        while q != p {
+            let x = self.top(q);
+            let d = self.down(q);
+            if x > self.n {
+                q = d;
+                mems += 1;
+            } else {
+                let u = self.up(q);
+                self.nodes[u].down = I::from_usize(q);
+                self.nodes[d].up = I::from_usize(q);
+                let size = self.top(x) + 1;
+                self.nodes[x].top = I::from_usize(size);
+                if C::TRACKS_COLUMNS {
+                    chooser.size_changed(x-1, self.branching(x));
+                }
+                q -= 1;
+                mems += 4;
+            }
+        }
+        mems
+    }
+
+    // Removes the whole of p's row from its columns, so that it can't be
+    // chosen at all.
+    fn hide_row<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let mems = self.hide(p, chooser) + 4;
+        self.count_mems(mems);
+        let (x, u, d) = (self.top(p), self.up(p), self.down(p));
+        self.nodes[u].down = I::from_usize(d);
+        self.nodes[d].up = I::from_usize(u);
+        self.nodes[x].top = I::from_usize(self.top(x) - 1);
+        if C::TRACKS_COLUMNS {
+            chooser.size_changed(x-1, self.branching(x));
+        }
+    }
+
+    // Undoes `hide_row(p)`.
+    fn unhide_row<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let (x, u, d) = (self.top(p), self.up(p), self.down(p));
+        self.nodes[u].down = I::from_usize(p);
+        self.nodes[d].up = I::from_usize(p);
+        self.nodes[x].top = I::from_usize(self.top(x) + 1);
+        if C::TRACKS_COLUMNS {
+            chooser.size_changed(x-1, self.branching(x));
+        }
+        let mems = self.unhide(p, chooser) + 4;
+        self.count_mems(mems);
+    }
+
+    // Whether node p has been taken out of its column, which happens when
+    // its row is hidden.
+    fn is_hidden(&self, p: usize) -> bool {
+        self.down(self.up(p)) != p
+    }
+
+    // Whether column c is still in the list of active columns.
+    fn is_active(&self, c: usize) -> bool {
+        self.right(self.left(c)) == c
+    }
+
+    // The first node of the row starting at node p in a primary column,
+    // if any.
+    fn primary_node(&self, mut p: usize) -> Option<usize> {
+        while self.top(p) <= self.n {
+            if self.top(p) <= self.primary {
+                return Some(p);
+            }
+            p += 1;
+        }
+        None
+    }
+
+    fn cover<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        let l = self.left(c);
+        let r = self.right(c);
+        self.headers[l].right = I::from_usize(r);
+        self.headers[r].left = I::from_usize(l);
+        if C::TRACKS_COLUMNS {
+            chooser.column_covered(c-1);
+        }
+
+        let mut p = self.down(c);
+        let mut mems = 3;
+        while p != c {
+            mems += self.hide(p, chooser) + 1;
+            p = self.down(p);
+        }
+        self.count_mems(mems);
+    }
+
+    fn uncover<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        let mut p = self.up(c);
+        let mut mems = 3;
+        while p != c {
+            mems += self.unhide(p, chooser) + 1;
+            p = self.up(p);
+        }
+        self.count_mems(mems);
+
+        let l = self.left(c);
+        let r = self.right(c);
+        self.headers[l].right = I::from_usize(c);
+        self.headers[r].left = I::from_usize(c);
+        if C::TRACKS_COLUMNS {
+            chooser.column_uncovered(c-1, self.branching(c));
+        }
+    }
+
+    // Hides every row giving p's column a different colour from p.
+    fn purify<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let c = self.colours[p];
+        let j = self.top(p);
+        self.purified_by[j] = I::from_usize(p);
+        let mut q = self.down(j);
+        while q != j {
+            if self.colours[q] != c {
+                let mems = self.hide(q, chooser);
+                self.count_mems(mems);
+            }
+            q = self.down(q);
+        }
+    }
+
+    // Undoes `purify(p)`.
+    fn unpurify<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let c = self.colours[p];
+        let j = self.top(p);
+        let mut q = self.up(j);
+        while q != j {
+            if self.colours[q] != c {
+                let mems = self.unhide(q, chooser);
+                self.count_mems(mems);
+            }
+            q = self.up(q);
+        }
+        self.purified_by[j] = I::from_usize(0);
+    }
+
+    // Covers p's column, or purifies it if p is coloured. A column
+    // already purified by an earlier row is left alone, as p's row
+    // could only have survived if it agrees on the colour. A primary
+    // column with multiplicities only has its bound reduced, and is
+    // covered once that reaches 0. Kept out of line so as not to slow
+    // down the plain loops that call it.
+    #[inline(never)]
+    fn commit<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let j = self.top(p);
+        if j <= self.primary && !self.bounds.is_empty() {
+            let bound = self.bounds[j].to_usize() - 1;
+            self.bounds[j] = I::from_usize(bound);
+            if bound == 0 {
+                self.cover(j, chooser);
+            } else if C::TRACKS_COLUMNS {
+                chooser.size_changed(j-1, self.branching(j));
+            }
+        } else if self.colours.is_empty() || self.colours[p].to_usize() == 0 {
+            self.cover(j, chooser);
+        } else if self.purified_by[j].to_usize() == 0 {
+            self.purify(p, chooser);
+        }
+    }
+
+    // Undoes `commit(p)`.
+    #[inline(never)]
+    fn uncommit<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        let j = self.top(p);
+        if j <= self.primary && !self.bounds.is_empty() {
+            let bound = self.bounds[j].to_usize();
+            if bound == 0 {
+                self.uncover(j, chooser);
+            }
+            self.bounds[j] = I::from_usize(bound + 1);
+            if C::TRACKS_COLUMNS {
+                chooser.size_changed(j-1, self.branching(j));
+            }
+        } else if self.colours.is_empty() || self.colours[p].to_usize() == 0 {
+            self.uncover(j, chooser);
+        } else if self.purified_by[j].to_usize() == p {
+            self.unpurify(p, chooser);
+        }
+    }
+
+    // The number of ways the search can branch on active primary column
+    // c: its number of rows, plus one for covering it no more times,
+    // minus however many of those rows must still be chosen for it.
+    fn branching(&self, c: usize) -> usize {
+        let len = self.top(c);
+        if self.bounds.is_empty() {
+            return len;
+        }
+        let (bound, slack) = (self.bounds[c].to_usize(), self.slacks[c].to_usize());
+        (len + 1 + slack).saturating_sub(bound.max(slack))
+    }
+
+    // How many more times active primary column c may be covered beyond
+    // the number of times it still must be.
+    fn slack(&self, c: usize) -> usize {
+        if self.bounds.is_empty() {
+            return 0;
+        }
+        self.bounds[c].to_usize().min(self.slacks[c].to_usize())
+    }
+
+    // Starts branching on column c with multiplicities, covering it if
+    // this is the last time it may be covered. Returns the first row
+    // to try, which is the first that will be tweaked.
+    fn begin_branch<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) -> usize {
+        let bound = self.bounds[c].to_usize() - 1;
+        self.bounds[c] = I::from_usize(bound);
+        if bound == 0 {
+            self.cover(c, chooser);
+        } else if C::TRACKS_COLUMNS {
+            chooser.size_changed(c-1, self.branching(c));
+        }
+        self.down(c)
+    }
+
+    // Decides what to try next when branching on column c, where x is
+    // the next row of the column or c itself if there are no more.
+    // Tweaks x out of the column if it's to be tried, or takes c out of
+    // the active columns if it's to be skipped.
+    fn next_branch<C: ColumnChooser>(
+        &mut self, c: usize, x: usize, chooser: &mut C,
+    ) -> Branch {
+        let bound = self.bounds[c].to_usize();
+        let slack = self.slacks[c].to_usize();
+        if bound == 0 && slack == 0 {
+            // As in plain Algorithm X: the column is covered and its rows
+            // are tried in turn.
+            return if x != c { Branch::Row(x) } else { Branch::Exhausted };
+        }
+        if self.top(c) + slack <= bound {
+            // Too few rows are left to reach the lower bound.
+            return Branch::Exhausted;
+        }
+        if x != c {
+            self.tweak(x, chooser);
+            Branch::Row(x)
+        } else {
+            if bound != 0 {
+                self.deactivate(c, chooser);
+            }
+            Branch::Skip
+        }
+    }
+
+    // Removes x, the first row of its column, from the column, hiding
+    // the rest of its row too unless the column is covered.
+    fn tweak<C: ColumnChooser>(&mut self, x: usize, chooser: &mut C) {
+        let c = self.top(x);
+        if self.bounds[c].to_usize() != 0 {
+            let mems = self.hide(x, chooser);
+            self.count_mems(mems);
+        }
+        let d = self.down(x);
+        self.nodes[c].down = I::from_usize(d);
+        self.nodes[d].up = I::from_usize(c);
+        self.nodes[c].top = I::from_usize(self.top(c) - 1);
+        if C::TRACKS_COLUMNS && self.bounds[c].to_usize() != 0 {
+            chooser.size_changed(c-1, self.branching(c));
+        }
+    }
+
+    // Undoes all the tweaks to column c since it began branching with
+    // first row `first`, and its covering, then restores its bound.
+    fn end_branch<C: ColumnChooser>(&mut self, c: usize, first: usize, chooser: &mut C) {
+        let bound = self.bounds[c].to_usize();
+        if bound == 0 && self.slacks[c].to_usize() == 0 {
+            self.uncover(c, chooser);
+        } else {
+            // The tweaked rows still link down to each other in order,
+            // ending at the column's current first row.
+            let z = self.down(c);
+            self.nodes[c].down = I::from_usize(first);
+            let (mut x, mut y) = (first, c);
+            let mut k = 0;
+            while x != z {
+                self.nodes[x].up = I::from_usize(y);
+                k += 1;
+                if bound != 0 {
+                    let mems = self.unhide(x, chooser);
+                    self.count_mems(mems);
+                }
+                y = x;
+                x = self.down(x);
+            }
+            self.nodes[z].up = I::from_usize(y);
+            self.nodes[c].top = I::from_usize(self.top(c) + k);
+            if bound == 0 {
+                self.uncover(c, chooser);
+            }
+        }
+        self.bounds[c] = I::from_usize(bound + 1);
+        if C::TRACKS_COLUMNS {
+            chooser.size_changed(c-1, self.branching(c));
+        }
+    }
+
+    // Takes c out of the active columns without hiding any rows.
+    fn deactivate<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        let l = self.left(c);
+        let r = self.right(c);
+        self.headers[l].right = I::from_usize(r);
+        self.headers[r].left = I::from_usize(l);
+        if C::TRACKS_COLUMNS {
+            chooser.column_covered(c-1);
+        }
+    }
+
+    // Undoes `deactivate(c)`, if it was done.
+    fn reactivate<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        if self.bounds[c].to_usize() == 0 {
+            return;
+        }
+        let l = self.left(c);
+        let r = self.right(c);
+        self.headers[l].right = I::from_usize(c);
+        self.headers[r].left = I::from_usize(c);
+        if C::TRACKS_COLUMNS {
+            chooser.column_uncovered(c-1, self.branching(c));
+        }
+    }
+
+    // Commits the columns of the other nodes in r's row, left to right.
+    fn cover_others<C: ColumnChooser>(&mut self, r: usize, chooser: &mut C) {
+        let plain = self.colours.is_empty() && self.bounds.is_empty();
+        let mut p = r + 1;
+        while p != r {
+            let j = self.top(p);
+            if j > self.n {
+                p = self.up(p);
+            } else {
+                if plain {
+                    self.cover(j, chooser);
+                } else {
+                    self.commit(p, chooser);
+                }
+                p += 1;
+            }
+        }
+    }
+
+    // Undoes `cover_others(r)`, uncommitting right to left.
+    fn uncover_others<C: ColumnChooser>(&mut self, r: usize, chooser: &mut C) {
+        let plain = self.colours.is_empty() && self.bounds.is_empty();
+        let mut p = r - 1;
+        while p != r {
+            let j = self.top(p);
+            if j > self.n {
+                p = self.down(p);
+            } else {
+                if plain {
+                    self.uncover(j, chooser);
+                } else {
+                    self.uncommit(p, chooser);
+                }
+                p -= 1;
+            }
+        }
+    }
+
+    // Whether node p can't be added to the current solution, because its
+    // column is covered or purified with a different colour.
+    fn conflicts(&self, p: usize) -> bool {
+        let j = self.top(p);
+        if self.right(self.left(j)) != j {
+            return true;
+        }
+        if self.colours.is_empty() {
+            return false;
+        }
+        let q = self.purified_by[j].to_usize();
+        q != 0 && self.colours[q] != self.colours[p]
+    }
+
+    // Whether any node of r's row conflicts with the current solution.
+    fn row_conflicts(&self, r: usize) -> bool {
+        if self.conflicts(r) {
+            return true;
+        }
+        let mut p = r + 1;
+        while p != r {
+            let j = self.top(p);
+            if j > self.n {
+                p = self.up(p);
+            } else if self.conflicts(p) {
+                return true;
+            } else {
+                p += 1;
+            }
+        }
+        false
+    }
+
+    // The active primary columns in order.
+    fn active_columns(&self) -> impl Iterator<Item = ColumnInfo> + '_ {
+        let mut j = self.right(0);
+        std::iter::from_fn(move || {
+            (j != 0).then(|| {
+                let c = ColumnInfo { col: j-1, size: self.branching(j) };
+                j = self.right(j);
+                c
+            })
+        })
+    }
+
+    // Replaces `nodes` with the nodes of column c from top to bottom.
+    fn column_nodes(&self, c: usize, nodes: &mut Vec<usize>) {
+        nodes.clear();
+        let mut p = self.down(c);
+        while p != c {
+            nodes.push(p);
+            p = self.down(p);
+        }
+    }
+
+    // Links the nodes of column c in the given order.
+    fn relink_column(&mut self, c: usize, column: &[usize]) {
+        let mut last = c;
+        for &p in column {
+            self.nodes[last].down = I::from_usize(p);
+            self.nodes[p].up = I::from_usize(last);
+            last = p;
+        }
+        self.nodes[last].down = I::from_usize(c);
+        self.nodes[c].up = I::from_usize(last);
+    }
+
+    // Randomly reorders the rows within every column. Only valid while
+    // nothing is covered.
+    fn shuffle_columns(&mut self, rng: &mut SplitMix64) {
+        let mut column = vec![];
+        for c in 1..=self.n {
+            self.column_nodes(c, &mut column);
+            for i in (1..column.len()).rev() {
+                column.swap(i, rng.below(i+1));
+            }
+            self.relink_column(c, &column);
+        }
+    }
+
+    // Stably sorts the rows within every column by cost, given the cost
+    // of each row by label. Only valid while nothing is covered.
+    fn sort_columns_by_cost(&mut self, costs: &[u64]) {
+        let mut column = vec![];
+        for c in 1..=self.n {
+            self.column_nodes(c, &mut column);
+            column.sort_by_key(|&p| costs[self.row_label(p)]);
+            self.relink_column(c, &column);
+        }
+    }
+
+    // A lower bound on the cost of covering the remaining primary
+    // columns, given the cost of each row by label and with every column
+    // sorted by cost: every column that still needs a row needs one
+    // costing at least as much as its first.
+    fn cost_lower_bound(&self, costs: &[u64]) -> u64 {
+        let mut bound = 0;
+        let mut j = self.right(0);
+        while j != 0 {
+            let needs_row = self.bounds.is_empty()
+                || self.bounds[j] > self.slacks[j];
+            let d = self.down(j);
+            if needs_row && d != j {
+                bound = bound.max(costs[self.row_label(d)]);
+            }
+            j = self.right(j);
+        }
+        bound
+    }
+
+    // Appends a description of the state of the columns: which are
+    // active and, for coloured problems, the colour each is purified
+    // with. Together these determine which rows remain.
+    fn state_key(&self, key: &mut Vec<u64>) {
+        let start = key.len();
+        key.resize(start + self.n/64 + 1, 0);
+        for c in 1..=self.n {
+            if self.right(self.left(c)) == c {
+                key[start + c/64] |= 1 << (c % 64);
+            }
+        }
+        if !self.colours.is_empty() {
+            key.extend(self.purified_by[1..].iter()
+                .map(|&p| self.colours[p.to_usize()].to_usize() as u64));
+        }
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.headers.capacity() * size_of::<Header<I>>()
+            + self.nodes.capacity() * size_of::<Node<I>>()
+            + (self.colours.len() + self.purified_by.capacity()
+                + self.bounds.capacity() + self.slacks.capacity())
+                * size_of::<I>()
+    }
+}
+
+/// The width of index used by a solver's dancing links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexWidth {
+    U16,
+    U32,
+    Usize,
+}
+
+impl IndexWidth {
+    /// The narrowest index width able to address the dancing links for
+    /// a problem of the given dimensions, of which `nonempty_rows` rows
+    /// contain at least one 1.
+    pub fn for_problem(
+        num_cols: usize,
+        num_rows: usize,
+        nonempty_rows: usize,
+        num_ones: usize,
+    ) -> Self {
+        // The last node index, and the largest spacer `top`.
+        let max_value = (num_cols + 1 + num_ones + nonempty_rows)
+            .max(num_cols + num_rows);
+        if max_value <= u16::MAX as usize {
+            IndexWidth::U16
+        } else if max_value <= u32::MAX as usize {
+            IndexWidth::U32
+        } else {
+            IndexWidth::Usize
+        }
+    }
+
+    /// The size of a single index in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            IndexWidth::U16 => size_of::<u16>(),
+            IndexWidth::U32 => size_of::<u32>(),
+            IndexWidth::Usize => size_of::<usize>(),
+        }
+    }
+
+    /// The number of bytes taken up by the dancing links of a problem
+    /// of the given dimensions at this width.
+    pub fn links_bytes(
+        self, num_cols: usize, nonempty_rows: usize, num_ones: usize,
+    ) -> usize {
+        let headers = num_cols + 2;
+        let nodes = num_cols + 1 + num_ones + nonempty_rows + 1;
+        (2*headers + 3*nodes) * self.bytes()
+    }
+}
+
+/// Dancing links of whichever index width the problem needs. All
+/// indices going in and out are `usize`s; the hot loops in `cover` and
+/// `uncover` are monomorphised for each width.
+#[derive(Debug, Clone)]
+pub(crate) enum Links {
+    U16(Dlx<u16>),
+    U32(Dlx<u32>),
+    Usize(Dlx<usize>),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $d:ident => $e:expr) => {
+        match $self {
+            Links::U16($d) => $e,
+            Links::U32($d) => $e,
+            Links::Usize($d) => $e,
+        }
+    };
+}
+
+impl Links {
+    pub(crate) fn new(problem: &ExactCoverProblem, nonempty_rows: usize) -> Self {
+        let width = IndexWidth::for_problem(
+            problem.columns(), problem.rows(), nonempty_rows,
+            problem.num_ones());
+        match width {
+            IndexWidth::U16 => Links::U16(Dlx::new(problem, nonempty_rows)),
+            IndexWidth::U32 => Links::U32(Dlx::new(problem, nonempty_rows)),
+            IndexWidth::Usize => Links::Usize(Dlx::new(problem, nonempty_rows)),
+        }
+    }
+
+    // The number of bytes the links for a problem take up, as reported by
+    // `memory_footprint`, worked out without building them.
+    pub(crate) fn bytes_for(problem: &ExactCoverProblem, nonempty_rows: usize) -> usize {
+        let n = problem.columns();
+        let ones = problem.num_ones();
+        let width = IndexWidth::for_problem(n, problem.rows(), nonempty_rows, ones);
+        // Coloured links have a colour for every node and the purifying
+        // row of every column, and links with multiplicities a bound and
+        // slack for every column.
+        let mut indices = 0;
+        if problem.is_coloured() {
+            indices += (n + 1 + ones + nonempty_rows + 1) + (n + 1);
+        }
+        if problem.has_multiplicities() {
+            indices += 2*(n + 1);
+        }
+        width.links_bytes(n, nonempty_rows, ones) + indices*width.bytes()
+    }
+
+    pub(crate) fn width(&self) -> IndexWidth {
+        match self {
+            Links::U16(_) => IndexWidth::U16,
+            Links::U32(_) => IndexWidth::U32,
+            Links::Usize(_) => IndexWidth::Usize,
+        }
+    }
+
+    /// The column header a node belongs to.
+    #[inline]
+    pub(crate) fn col(&self, x: usize) -> usize { dispatch!(self, d => d.top(x)) }
+    #[inline]
+    pub(crate) fn down(&self, x: usize) -> usize { dispatch!(self, d => d.down(x)) }
+    #[inline]
+    pub(crate) fn right(&self, c: usize) -> usize { dispatch!(self, d => d.right(c)) }
+    pub(crate) fn row_label(&self, x: usize) -> usize {
+        dispatch!(self, d => d.row_label(x))
+    }
+    pub(crate) fn cover<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        dispatch!(self, d => d.cover(c, chooser))
+    }
+    pub(crate) fn uncover<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        dispatch!(self, d => d.uncover(c, chooser))
+    }
+    pub(crate) fn cover_others<C: ColumnChooser>(&mut self, r: usize, chooser: &mut C) {
+        dispatch!(self, d => d.cover_others(r, chooser))
+    }
+    pub(crate) fn uncover_others<C: ColumnChooser>(&mut self, r: usize, chooser: &mut C) {
+        dispatch!(self, d => d.uncover_others(r, chooser))
+    }
+    /// Covers or purifies the column of node p, as appropriate.
+    pub(crate) fn commit<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        dispatch!(self, d => d.commit(p, chooser))
+    }
+    pub(crate) fn uncommit<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        dispatch!(self, d => d.uncommit(p, chooser))
+    }
+    /// Whether some primary column may be covered other than once.
+    pub(crate) fn has_multiplicities(&self) -> bool {
+        dispatch!(self, d => !d.bounds.is_empty())
+    }
+    /// Whether x is a node of a row rather than a column header.
+    pub(crate) fn is_row_node(&self, x: usize) -> bool {
+        dispatch!(self, d => x > d.n)
+    }
+    /// Whether c is the header of a primary column.
+    pub(crate) fn is_primary_column(&self, c: usize) -> bool {
+        dispatch!(self, d => 1 <= c && c <= d.primary)
+    }
+    /// Whether x is a node of a row, rather than a header or a spacer.
+    pub(crate) fn is_entry(&self, x: usize) -> bool {
+        dispatch!(self, d => d.n < x && x < d.nodes.len() && d.top(x) <= d.n)
+    }
+    #[cfg(feature = "stats")]
+    pub(crate) fn mems(&self) -> u64 {
+        dispatch!(self, d => d.mems)
+    }
+    pub(crate) fn slack(&self, c: usize) -> usize {
+        dispatch!(self, d => d.slack(c))
+    }
+    pub(crate) fn begin_branch<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) -> usize {
+        dispatch!(self, d => d.begin_branch(c, chooser))
+    }
+    pub(crate) fn next_branch<C: ColumnChooser>(
+        &mut self, c: usize, x: usize, chooser: &mut C,
+    ) -> Branch {
+        dispatch!(self, d => d.next_branch(c, x, chooser))
+    }
+    pub(crate) fn end_branch<C: ColumnChooser>(&mut self, c: usize, first: usize, chooser: &mut C) {
+        dispatch!(self, d => d.end_branch(c, first, chooser))
+    }
+    pub(crate) fn reactivate<C: ColumnChooser>(&mut self, c: usize, chooser: &mut C) {
+        dispatch!(self, d => d.reactivate(c, chooser))
+    }
+    pub(crate) fn row_conflicts(&self, r: usize) -> bool {
+        dispatch!(self, d => d.row_conflicts(r))
+    }
+    pub(crate) fn hide_row<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        dispatch!(self, d => d.hide_row(p, chooser))
+    }
+    pub(crate) fn unhide_row<C: ColumnChooser>(&mut self, p: usize, chooser: &mut C) {
+        dispatch!(self, d => d.unhide_row(p, chooser))
+    }
+    pub(crate) fn is_hidden(&self, p: usize) -> bool {
+        dispatch!(self, d => d.is_hidden(p))
+    }
+    pub(crate) fn is_active(&self, c: usize) -> bool {
+        dispatch!(self, d => d.is_active(c))
+    }
+    pub(crate) fn primary_node(&self, p: usize) -> Option<usize> {
+        dispatch!(self, d => d.primary_node(p))
+    }
+    /// Tells a chooser about the initial active primary columns.
+    pub(crate) fn init_chooser<C: ColumnChooser>(&self, chooser: &mut C) {
+        dispatch!(self, d => chooser.init(d.active_columns()))
+    }
+    /// Has the chooser pick an active primary column, returning its
+    /// header and size. There must be at least one active column.
+    pub(crate) fn choose<C: ColumnChooser>(&self, chooser: &mut C) -> (usize, usize) {
+        let c = dispatch!(self, d => chooser.choose(d.active_columns()));
+        (c.col + 1, c.size)
+    }
+    pub(crate) fn shuffle_columns(&mut self, rng: &mut SplitMix64) {
+        dispatch!(self, d => d.shuffle_columns(rng))
+    }
+    pub(crate) fn sort_columns_by_cost(&mut self, costs: &[u64]) {
+        dispatch!(self, d => d.sort_columns_by_cost(costs))
+    }
+    pub(crate) fn cost_lower_bound(&self, costs: &[u64]) -> u64 {
+        dispatch!(self, d => d.cost_lower_bound(costs))
+    }
+    pub(crate) fn state_key(&self, key: &mut Vec<u64>) {
+        dispatch!(self, d => d.state_key(key))
+    }
+    pub(crate) fn memory_footprint(&self) -> usize {
+        dispatch!(self, d => d.memory_footprint())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::problems::NQueens;
+    use crate::solver::{
+        ExactCoverRepresentable, ExactCoverSolver, SparseBinaryMatrix,
+    };
+
+    #[test]
+    fn test_index_width_for_problem() {
+        assert_eq!(IndexWidth::for_problem(100, 100, 100, 1000), IndexWidth::U16);
+        assert_eq!(IndexWidth::for_problem(4096, 4096, 4096, 57000), IndexWidth::U16);
+        assert_eq!(IndexWidth::for_problem(4096, 4096, 4096, 58000), IndexWidth::U32);
+        // Empty rows take no nodes, but their labels must still fit.
+        assert_eq!(IndexWidth::for_problem(10, 70000, 5, 20), IndexWidth::U32);
+    }
+
+    #[test]
+    fn test_layout_row_traversal() {
+        let o = false; let x = true;
+        let arrays = [
+            [o,x,o,x],
+            [o,o,o,o],
+            [x,x,x,o],
+        ];
+        let problem = ExactCoverProblem::new_general(
+            SparseBinaryMatrix::from_array_2d(arrays), 1).unwrap();
+        let links = Links::new(&problem, 2);
+        assert_eq!(links.width(), IndexWidth::U16);
+        // Nodes 1-4 are headers, 5 a spacer, 6-7 row 0, 8 a spacer,
+        // 9-11 row 2 and 12 a spacer.
+        assert_eq!((6..=7).map(|x| links.col(x)).collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!((9..=11).map(|x| links.col(x)).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!((6..=7).map(|x| links.row_label(x)).collect::<Vec<_>>(), vec![0, 0]);
+        assert_eq!((9..=11).map(|x| links.row_label(x)).collect::<Vec<_>>(), vec![2, 2, 2]);
+        // Column 2 (header 2) has rows 0 and 2.
+        assert_eq!(links.down(2), 6);
+        assert_eq!(links.down(6), 10);
+        assert_eq!(links.down(10), 2);
+        // Primary list is 0, 1, 2, 3; secondary list is 5, 4.
+        assert_eq!(links.right(0), 1);
+        assert_eq!(links.right(3), 0);
+        assert_eq!(links.right(5), 4);
+        assert_eq!(links.right(4), 5);
+    }
+
+    #[test]
+    fn test_memory_reqs_is_true_footprint() {
+        let problem = NQueens::new(8).exact_cover_problem();
+        let solver = ExactCoverSolver::new(&problem);
+        assert_eq!(solver.index_width(), IndexWidth::U16);
+        assert_eq!(
+            ExactCoverSolver::memory_reqs(&problem),
+            solver.memory_footprint(),
+        );
+    }
+}
@@ -3,15 +3,52 @@
 //! TODO: an example of calling the solver.
 
 mod problem;
+mod assumptions;
+mod budget;
+mod checkpoint;
+mod chooser;
+mod decompose;
+mod estimate;
+mod handle;
+mod infeasible;
 mod iterators;
+mod links;
 mod output;
+mod parallel;
+mod reduce;
 mod representable;
+mod rng;
+#[allow(clippy::module_inception)]
 mod solver;
+#[cfg(feature = "stats")]
+mod stats;
+mod subtree;
+mod symmetry;
 mod tests;
+mod zdd;
 
+pub use assumptions::AssumptionError;
+pub use budget::SearchBudget;
+pub use checkpoint::CheckpointError;
 pub use problem::{ExactCoverProblem, ExactCoverProblemError,
 SparseBinaryMatrix};
-pub use iterators::{Solutions, SolverSteps};
-pub use output::{ExactCover, PartialCover, SolverStep};
+pub use decompose::{Component, Decomposition};
+pub use estimate::{Estimate, TreeSizeEstimate};
+pub use handle::SolverHandle;
+pub use infeasible::Infeasibility;
+pub use chooser::{
+    ColumnChooser, ColumnInfo, FirstColumn, Mrv, MrvBuckets, MrvRandomTies,
+    Priority,
+};
+pub use iterators::{Solutions, SolutionsByCost, SolverSteps};
+pub use output::{ExactCover, PartialCover, SearchOutcome, SolverStep};
+pub use parallel::{count_solutions_parallel, ParallelSolver};
+pub use reduce::Reduction;
 pub use solver::ExactCoverSolver;
+#[cfg(feature = "stats")]
+pub use stats::SolverStats;
+pub use subtree::{SubtreeError, SubtreePath};
+pub use symmetry::Symmetry;
+pub use links::IndexWidth;
 pub use representable::ExactCoverRepresentable;
+pub use zdd::SolutionZdd;
@@ -53,14 +53,23 @@ impl PartialCover {
 /// These invariants are tested in a comprehensive test suite.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SolverStep {
-    /// Choose a column (constraint) to enumerate over. The solver always
-    /// chooses the lowest-indexed column with the fewest satisfying choices.
+    /// Choose a column (constraint) to enumerate over. The column is
+    /// picked by the solver's [`ColumnChooser`](super::ColumnChooser);
+    /// by default this is the lowest-indexed column with the fewest
+    /// satisfying choices.
     SelectColumn {
         /// The index of the chosen column.
         col: usize,
-        /// The number of rows with 1s for this column. This will
-        /// be minimal among all columns.
+        /// The number of rows with 1s for this column. Under the default
+        /// chooser this will be minimal among all columns. For problems
+        /// with multiplicities this is instead the number of ways to
+        /// branch on the column: its rows, plus one for covering it no
+        /// more times, less the number of rows it still needs.
         size: usize,
+        /// How many more times the column may be covered beyond the
+        /// number of times it still must be. Always 0 for problems
+        /// without multiplicities.
+        slack: usize,
     },
     /// Finished enumerating over this column; bin it.
     DeselectColumn(usize),
@@ -72,4 +81,25 @@ pub enum SolverStep {
     PopRow(usize),
     /// Reports a complete solution.
     ReportSolution(ExactCover),
+    /// A randomised solver used up its node budget without finding a
+    /// solution, abandoned its current path and restarted from the top
+    /// with a new random order. Both the row and column stacks are
+    /// emptied.
+    Restart,
+}
+
+/// The outcome of a search for the next solution within a
+/// [`SearchBudget`](super::SearchBudget).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchOutcome {
+    /// A solution was found.
+    Solution(ExactCover),
+    /// The budget ran out first. The search can be carried on from where
+    /// it stopped with a fresh budget.
+    BudgetExhausted,
+    /// The search was cancelled through a
+    /// [`SolverHandle`](super::SolverHandle).
+    Cancelled,
+    /// There are no more solutions.
+    Finished,
 }
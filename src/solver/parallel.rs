@@ -0,0 +1,265 @@
+//! Multi-threaded enumeration and counting.
+//!
+//! The top of the search tree is expanded down to a shallow depth, and
+//! the subtrees below it are handed out to worker threads one at a time,
+//! each of which searches them with a solver of its own. Subtrees vary
+//! wildly in size, so the split aims for many more subtrees than threads
+//! to keep every thread busy until the end.
+//!
+//! With the `rayon` feature, counting runs on a rayon thread pool
+//! instead of plain std threads.
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+
+use super::{
+    chooser::{ColumnChooser, Mrv},
+    subtree::SubtreePath,
+    ExactCover, ExactCoverProblem, ExactCoverSolver, SolverStep,
+};
+
+/// The number of subtrees per thread to aim for when splitting the
+/// search tree automatically.
+const SUBTREES_PER_THREAD: usize = 32;
+
+/// The deepest the search tree is split automatically.
+const MAX_SPLIT_DEPTH: usize = 8;
+
+/// The number of solutions each worker may get ahead of the consumer of
+/// a [`ParallelSolutions`] iterator.
+const CHANNEL_BOUND: usize = 64;
+
+/// A driver solving an exact cover problem on several threads.
+///
+/// Every solution is found exactly once, but the order they are found
+/// in depends on the timing of the threads. Problems with multiplicities
+/// aren't supported. Costs are ignored, so every solution is found. If
+/// the problem has symmetries, only the canonical solution of each class
+/// is found, just as by [`ExactCoverSolver`], so counts are of classes;
+/// summing [`orbit_size`](ExactCoverSolver::orbit_size) over the
+/// solutions found gives the total.
+pub struct ParallelSolver<'a, C = Mrv> {
+    problem: &'a ExactCoverProblem,
+    chooser: C,
+    threads: usize,
+    split_depth: Option<usize>,
+}
+
+impl<'a> ParallelSolver<'a> {
+    /// Creates a parallel solver for a problem, using the default
+    /// column choice rule and as many threads as the machine has.
+    pub fn new(problem: &'a ExactCoverProblem) -> Self {
+        Self::with_chooser(problem, Mrv)
+    }
+}
+
+impl<'a, C: ColumnChooser + Clone + Send + Sync + 'static> ParallelSolver<'a, C> {
+    /// Creates a parallel solver for a problem, choosing columns to
+    /// branch on with a copy of the given chooser in each thread.
+    pub fn with_chooser(problem: &'a ExactCoverProblem, chooser: C) -> Self {
+        let threads = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get);
+        Self { problem, chooser, threads, split_depth: None }
+    }
+
+    /// Sets the number of worker threads. Zero is treated as one.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Sets the depth of the search tree at which to split it into
+    /// subtrees for the workers, or `None` (the default) to split it
+    /// just deep enough for there to be plenty of subtrees per thread.
+    pub fn set_split_depth(&mut self, depth: Option<usize>) {
+        self.split_depth = depth;
+    }
+
+    /// Counts every solution, or every class of solutions if the problem
+    /// has symmetries, or returns `None` if the count doesn't fit in a
+    /// `u128`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the problem has multiplicities.
+    pub fn count_solutions(&self) -> Option<u128> {
+        let mut solver = self.solver();
+        let paths = self.split(&mut solver);
+        // Symmetries can map solutions with different subsets of the
+        // empty rows to one another, so then each subset has to be
+        // checked for being canonical along with the rest of its rows.
+        if self.problem.has_symmetries() {
+            return self.count_subtree_solutions(&solver, &paths);
+        }
+        let multiplier = solver.empty_row_multiplier()?;
+        solver.set_expand_empty_rows(false);
+        self.count_subtree_solutions(&solver, &paths)?.checked_mul(multiplier)
+    }
+
+    /// Starts searching for solutions in the background, returning an
+    /// iterator through them as they are found. Dropping the iterator
+    /// stops the search.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the problem has multiplicities.
+    pub fn iter_solutions(&self) -> ParallelSolutions {
+        let mut solver = self.solver();
+        let paths = Arc::new(self.split(&mut solver));
+        let next = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_BOUND);
+
+        // Each worker gets its own fork of the solver.
+        let forks = (1..self.threads).map(|_| solver.fork()).collect::<Vec<_>>();
+        let mut workers = Vec::with_capacity(self.threads);
+        for mut solver in std::iter::once(solver).chain(forks) {
+            let (paths, next, stop) = (paths.clone(), next.clone(), stop.clone());
+            let sender = sender.clone();
+            workers.push(thread::spawn(move || {
+                while let Some(path) = claim(&paths, &next) {
+                    solver.restrict_to(path).unwrap();
+                    // Checking every step lets a dropped iterator stop
+                    // workers stuck in subtrees without solutions.
+                    while let Some(step) = solver.next_step() {
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        if let SolverStep::ReportSolution(solution) = step
+                            && sender.send(solution).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }));
+        }
+        ParallelSolutions { receiver: Some(receiver), stop, workers }
+    }
+
+    fn solver(&self) -> ExactCoverSolver<C> {
+        ExactCoverSolver::with_chooser(self.problem, self.chooser.clone())
+    }
+
+    // The paths to the roots of the subtrees to hand out.
+    fn split(&self, solver: &mut ExactCoverSolver<C>) -> Vec<SubtreePath> {
+        if let Some(depth) = self.split_depth {
+            return solver.frontier(depth);
+        }
+        let target = self.threads.saturating_mul(SUBTREES_PER_THREAD);
+        let mut paths = solver.frontier(0);
+        for depth in 1..=MAX_SPLIT_DEPTH {
+            // Stop once there are enough subtrees or the whole tree is
+            // above this depth.
+            if self.threads == 1 || paths.len() >= target
+                || paths.iter().all(|p| p.depth() < depth - 1) {
+                break;
+            }
+            paths = solver.frontier(depth);
+        }
+        paths
+    }
+
+    // The number of solutions in the subtrees found by forks of the
+    // given solver.
+    #[cfg(not(feature = "rayon"))]
+    fn count_subtree_solutions(
+        &self, solver: &ExactCoverSolver<C>, paths: &[SubtreePath],
+    ) -> Option<u128> {
+        let next = AtomicUsize::new(0);
+        thread::scope(|s| {
+            let workers = (0..self.threads)
+                .map(|_| s.spawn(|| {
+                    let mut solver = solver.fork();
+                    let mut count = 0u128;
+                    while let Some(path) = claim(paths, &next) {
+                        solver.restrict_to(path).unwrap();
+                        count = count.checked_add(solver.count_solutions())?;
+                    }
+                    Some(count)
+                }))
+                .collect::<Vec<_>>();
+            workers.into_iter()
+                .map(|w| w.join().unwrap())
+                .try_fold(0u128, |total, count| total.checked_add(count?))
+        })
+    }
+
+    // The number of solutions in the subtrees found by forks of the
+    // given solver.
+    #[cfg(feature = "rayon")]
+    fn count_subtree_solutions(
+        &self, solver: &ExactCoverSolver<C>, paths: &[SubtreePath],
+    ) -> Option<u128> {
+        use rayon::prelude::*;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+            .expect("failed to build thread pool");
+        pool.install(|| paths.par_iter()
+            .map_init(
+                || solver.fork(),
+                |solver, path| {
+                    solver.restrict_to(path).unwrap();
+                    Some(solver.count_solutions())
+                })
+            .try_reduce(|| 0, |a, b| a.checked_add(b)))
+    }
+}
+
+// Hands out the next unclaimed subtree, if any are left.
+fn claim<'p>(
+    paths: &'p [SubtreePath], next: &AtomicUsize,
+) -> Option<&'p SubtreePath> {
+    paths.get(next.fetch_add(1, Ordering::Relaxed))
+}
+
+/// Counts every solution of a problem using the given number of threads,
+/// or every class of solutions if it has symmetries, or returns `None`
+/// if the count doesn't fit in a `u128`. See [`ParallelSolver`] for more
+/// control.
+///
+/// # Panics
+///
+/// Panics if the problem has multiplicities.
+pub fn count_solutions_parallel(
+    problem: &ExactCoverProblem, threads: usize,
+) -> Option<u128> {
+    let mut solver = ParallelSolver::new(problem);
+    solver.set_threads(threads);
+    solver.count_solutions()
+}
+
+/// An iterator through the solutions found by a [`ParallelSolver`] as the
+/// workers find them. This `struct` is created by the
+/// [`iter_solutions`](ParallelSolver::iter_solutions) method on
+/// [`ParallelSolver`]. Dropping it stops the workers and waits for them
+/// to finish.
+#[derive(Debug)]
+pub struct ParallelSolutions {
+    receiver: Option<Receiver<ExactCover>>,
+    stop: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Iterator for ParallelSolutions {
+    type Item = ExactCover;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.as_ref()?.recv().ok()
+    }
+}
+
+impl Drop for ParallelSolutions {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Unblocks any worker waiting to send.
+        self.receiver = None;
+        for worker in self.workers.drain(..) {
+            // A panicking worker has already reported its panic.
+            let _ = worker.join();
+        }
+    }
+}
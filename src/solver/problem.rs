@@ -1,7 +1,22 @@
+use super::{Decomposition, ExactCover, Infeasibility, Reduction, Symmetry};
+use super::symmetry;
+use super::checkpoint::Fnv1a;
+
 /// Specification of a generalised exact cover problem.
 pub struct ExactCoverProblem {
     pub matrix: SparseBinaryMatrix,
     num_secondary_columns: usize,
+    // The bounds (lo, hi) on how many times each primary column must be
+    // covered, or empty if every primary column must be covered once.
+    multiplicities: Vec<(usize, usize)>,
+    // The cost of each row, or empty if every row costs 0.
+    costs: Vec<u64>,
+    // The row permutations of the symmetry group, other than the
+    // identity, or empty if no symmetries were given.
+    symmetries: Vec<Vec<usize>>,
+    // The symmetries as given, to check again if the costs or
+    // multiplicities change.
+    symmetry_generators: Vec<Symmetry>,
 }
 
 /// Something that can go wrong while constructing an
@@ -16,32 +31,129 @@ pub enum ExactCoverProblemError {
     /// The number of secondary columns passed in was greater than
     /// the number of columns passed in.
     TooManySecondaryColumns,
+    /// A 1 in a primary column was given a colour. Only entries in
+    /// secondary columns may be coloured.
+    ColouredPrimaryColumn { row_idx: usize, col_idx: usize },
+    /// The number of multiplicities passed in was not the number of
+    /// primary columns.
+    WrongNumberOfMultiplicities,
+    /// The multiplicity `(lo, hi)` of a primary column had `lo > hi` or
+    /// `hi == 0`.
+    InvalidMultiplicity { col_idx: usize },
+    /// The number of costs passed in was not the number of rows.
+    WrongNumberOfCosts,
+    /// The symmetry at the given index was not a permutation of the rows
+    /// and columns, or did not map the problem to itself.
+    InvalidSymmetry { index: usize },
+    /// The symmetries generated a group with more than
+    /// [`Symmetry::MAX_GROUP_SIZE`] elements.
+    SymmetryGroupTooLarge,
 }
 
 impl ExactCoverProblem {
     /// Creates a generalised exact cover problem from a sparse
     /// binary matrix and the number of columns and secondary columns.
-    /// Returns an Ok under
+    /// Returns an error if the rows are not valid sparse rows or if
+    /// there are more secondary columns than columns.
     pub fn new(
         row_indices: impl Iterator<Item = impl Iterator<Item = usize>>,
         num_columns: usize,
         num_secondary_columns: usize,
     ) -> Result<Self, ExactCoverProblemError> {
-        // TODO: Don't unwrap?!
         let matrix = SparseBinaryMatrix::from_sparse_rows(
-            row_indices, num_columns).unwrap();
+            row_indices, num_columns)?;
+        Self::new_general(matrix, num_secondary_columns)
+    }
+
+    /// Creates an exact cover problem with colours (Knuth's "XCC"
+    /// model) from rows of `(column, colour)` pairs. Colour 0 means
+    /// uncoloured; any other colour may only be given to an entry in a
+    /// secondary column.
+    ///
+    /// An uncoloured secondary column may be covered at most once, as
+    /// usual. A secondary column may instead be covered by any number of
+    /// rows if they all give it the same nonzero colour.
+    pub fn new_coloured(
+        row_entries: impl Iterator<Item = impl Iterator<Item = (usize, usize)>>,
+        num_columns: usize,
+        num_secondary_columns: usize,
+    ) -> Result<Self, ExactCoverProblemError> {
+        let matrix = SparseBinaryMatrix::from_coloured_sparse_rows(
+            row_entries, num_columns)?;
+        Self::new_general(matrix, num_secondary_columns)
+    }
+
+    /// Creates a generalised exact cover problem from a matrix, whose
+    /// last `num_secondary_columns` columns are secondary. Returns an
+    /// error if there are more secondary columns than columns or if an
+    /// entry in a primary column is coloured.
+    pub fn new_general(matrix: SparseBinaryMatrix,
+        num_secondary_columns: usize,
+    ) -> Result<Self, ExactCoverProblemError> {
         if num_secondary_columns > matrix.num_cols {
-            Err(ExactCoverProblemError::TooManySecondaryColumns)
+            return Err(ExactCoverProblemError::TooManySecondaryColumns);
+        }
+        let primary = matrix.num_cols - num_secondary_columns;
+        for (row_idx, row) in matrix.ordered_coloured_rows().enumerate() {
+            for (col_idx, colour) in row {
+                if colour != 0 && col_idx < primary {
+                    return Err(ExactCoverProblemError::ColouredPrimaryColumn {
+                        row_idx, col_idx,
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            matrix,
+            num_secondary_columns,
+            multiplicities: vec![],
+            costs: vec![],
+            symmetries: vec![],
+            symmetry_generators: vec![],
+        })
+    }
+
+    /// Sets how many times each primary column must be covered, from a
+    /// `(lo, hi)` pair for each primary column in order. A solution must
+    /// then cover each primary column at least `lo` and at most `hi`
+    /// times (Knuth's Algorithm M). The default is `(1, 1)` for every
+    /// primary column, i.e. an exact cover. If symmetries have been set
+    /// and one of them no longer maps the problem to itself, returns
+    /// [`InvalidSymmetry`](ExactCoverProblemError::InvalidSymmetry) and
+    /// leaves the problem unchanged.
+    pub fn set_multiplicities(
+        &mut self, multiplicities: Vec<(usize, usize)>,
+    ) -> Result<(), ExactCoverProblemError> {
+        if multiplicities.len() != self.primary_columns() {
+            return Err(ExactCoverProblemError::WrongNumberOfMultiplicities);
+        }
+        for (col_idx, &(lo, hi)) in multiplicities.iter().enumerate() {
+            if lo > hi || hi == 0 {
+                return Err(ExactCoverProblemError::InvalidMultiplicity { col_idx });
+            }
+        }
+        let multiplicities = match multiplicities.iter().all(|&m| m == (1, 1)) {
+            true => vec![],
+            false => multiplicities,
+        };
+        let old = std::mem::replace(&mut self.multiplicities, multiplicities);
+        self.recheck_symmetries().inspect_err(|_| self.multiplicities = old)
+    }
+
+    /// The bounds `(lo, hi)` on how many times the given column must be
+    /// covered. This is `(0, 1)` for a secondary column.
+    pub fn multiplicity(&self, col: usize) -> (usize, usize) {
+        if col >= self.primary_columns() {
+            (0, 1)
         } else {
-            Ok(Self { matrix, num_secondary_columns })
+            self.multiplicities.get(col).copied().unwrap_or((1, 1))
         }
     }
 
-    // TODO: delete or replace the other one with this
-    pub fn new_general(matrix: SparseBinaryMatrix,
-        num_secondary_columns: usize) -> Result<Self, ()> {
-        // todo check validity
-        Ok(Self { matrix, num_secondary_columns })
+    /// Whether any primary column may be covered other than exactly once.
+    #[inline]
+    pub fn has_multiplicities(&self) -> bool {
+        !self.multiplicities.is_empty()
     }
 
 
@@ -59,6 +171,129 @@ impl ExactCoverProblem {
             })
     }
 
+    /// Returns an iterator of rows, which are themselves iterators over
+    /// the `(column, colour)` pairs of the 1s of the array. The colour is
+    /// 0 for uncoloured entries.
+    pub fn ordered_coloured_rows(&self)
+        -> impl Iterator<Item = impl Iterator<Item = (usize, usize)> + '_>
+    {
+        self.matrix.ordered_coloured_rows()
+    }
+
+    /// Whether any entry of this problem is coloured.
+    #[inline]
+    pub fn is_coloured(&self) -> bool {
+        self.matrix.is_coloured()
+    }
+
+    /// Sets the cost of each row. The cost of a solution is the total
+    /// cost of its rows, and the solver can search for the cheapest
+    /// solutions by branch and bound. Every row costs 0 by default. If
+    /// symmetries have been set and one of them no longer maps the
+    /// problem to itself, returns
+    /// [`InvalidSymmetry`](ExactCoverProblemError::InvalidSymmetry) and
+    /// leaves the problem unchanged.
+    pub fn set_costs(&mut self, costs: Vec<u64>) -> Result<(), ExactCoverProblemError> {
+        if costs.len() != self.rows() {
+            return Err(ExactCoverProblemError::WrongNumberOfCosts);
+        }
+        let costs = match costs.iter().all(|&c| c == 0) {
+            true => vec![],
+            false => costs,
+        };
+        let old = std::mem::replace(&mut self.costs, costs);
+        self.recheck_symmetries().inspect_err(|_| self.costs = old)
+    }
+
+    /// The cost of the given row.
+    pub fn cost(&self, row: usize) -> u64 {
+        self.costs.get(row).copied().unwrap_or(0)
+    }
+
+    /// Whether any row has a nonzero cost.
+    #[inline]
+    pub fn has_costs(&self) -> bool {
+        !self.costs.is_empty()
+    }
+
+    /// The total cost of the rows of a solution.
+    pub fn solution_cost(&self, solution: &ExactCover) -> u64 {
+        solution.0.iter().map(|&r| self.cost(r)).sum()
+    }
+
+    /// Sets symmetries of the problem, which the solver uses to report
+    /// only one solution from each class of solutions that symmetries map
+    /// to one another. The symmetries need only generate the group: every
+    /// combination of them is worked out and stored here, taking a
+    /// `usize` per row for each element of the group. Returns an error if
+    /// one doesn't map the problem, including its colours, costs and
+    /// multiplicities, to itself, or if the group has more than
+    /// [`Symmetry::MAX_GROUP_SIZE`] elements. The symmetries are checked
+    /// again whenever the costs or multiplicities are set.
+    ///
+    /// Symmetries don't make the search any smaller: the solver still
+    /// finds every solution, and compares each against all of its images
+    /// under the group to pass over those that aren't canonical.
+    pub fn set_symmetries(
+        &mut self, symmetries: Vec<Symmetry>,
+    ) -> Result<(), ExactCoverProblemError> {
+        self.check_symmetries(&symmetries)?;
+        let generators = symmetries.iter().map(|s| s.rows()).collect::<Vec<_>>();
+        self.symmetries = symmetry::close_group(&generators, self.rows())
+            .ok_or(ExactCoverProblemError::SymmetryGroupTooLarge)?;
+        self.symmetry_generators = symmetries;
+        Ok(())
+    }
+
+    // Checks that each of the given symmetries maps the problem to itself.
+    fn check_symmetries(&self, symmetries: &[Symmetry]) -> Result<(), ExactCoverProblemError> {
+        let rows = self.ordered_coloured_rows()
+            .map(|row| row.collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        match symmetries.iter().position(|s| !self.is_symmetry(s, &rows)) {
+            Some(index) => Err(ExactCoverProblemError::InvalidSymmetry { index }),
+            None => Ok(()),
+        }
+    }
+
+    // Checks the symmetries set earlier still map the problem to itself.
+    fn recheck_symmetries(&self) -> Result<(), ExactCoverProblemError> {
+        self.check_symmetries(&self.symmetry_generators)
+    }
+
+    // Whether a symmetry maps the problem to itself, given the entries of
+    // each row.
+    fn is_symmetry(&self, s: &Symmetry, rows: &[Vec<(usize, usize)>]) -> bool {
+        if !symmetry::is_permutation(s.rows(), self.rows())
+            || !symmetry::is_permutation(s.columns(), self.columns())
+        {
+            return false;
+        }
+        let primary = self.primary_columns();
+        let columns_fit = (0..self.columns()).all(|j| {
+            let k = s.column(j);
+            (j < primary) == (k < primary) && self.multiplicity(j) == self.multiplicity(k)
+        });
+        columns_fit && rows.iter().enumerate().all(|(r, row)| {
+            let mut image = row.iter()
+                .map(|&(j, colour)| (s.column(j), colour))
+                .collect::<Vec<_>>();
+            image.sort_unstable();
+            image == rows[s.row(r)] && self.cost(r) == self.cost(s.row(r))
+        })
+    }
+
+    /// Whether any symmetries other than the identity were set.
+    #[inline]
+    pub fn has_symmetries(&self) -> bool {
+        !self.symmetries.is_empty()
+    }
+
+    // Every row permutation of the symmetry group but the identity.
+    pub(crate) fn symmetry_group(&self) -> &[Vec<usize>] {
+        &self.symmetries
+    }
+
     // /// Gets the state with the given row and column index.
     // /// Returns None if the indices are out of bounds.
     // pub fn get(&self, row_idx: usize, col_idx: usize) -> Option<bool> {
@@ -89,6 +324,67 @@ impl ExactCoverProblem {
     pub fn num_ones(&self) -> usize {
         self.matrix.cols.len()
     }
+
+    /// Shrinks the problem by repeatedly taking out rows forced into
+    /// every solution, rows that can never be chosen, and columns made
+    /// redundant by others, returning the smaller problem together with
+    /// the [`Reduction`] that lifts its solutions back to solutions of
+    /// this one. Every solution of this problem is the lift of exactly
+    /// one solution of the reduced problem.
+    ///
+    /// Problems with multiplicities come back unreduced, as a row can
+    /// only be forced when each column is covered exactly once. The only
+    /// change to them is dropping secondary columns without any rows.
+    pub fn reduce(&self) -> (ExactCoverProblem, Reduction) {
+        super::reduce::reduce(self)
+    }
+
+    /// Splits the problem into independent components, which share no
+    /// columns, so that each can be solved on its own. Every solution of
+    /// the problem is a solution of each component put together.
+    pub fn decompose(&self) -> Decomposition {
+        super::decompose::decompose(self)
+    }
+
+    /// Explains why the problem has no solutions, by finding the primary
+    /// columns without rows, those left without rows once the forced
+    /// rows are chosen, and a minimal set of columns that can't be
+    /// covered even on their own. Returns `None` if the problem has a
+    /// solution. Costs are ignored. This solves many restrictions of the
+    /// problem, so may take a while.
+    pub fn explain_infeasibility(&self) -> Option<Infeasibility> {
+        super::infeasible::explain_infeasibility(self)
+    }
+
+    // A hash of everything about the problem that affects the search,
+    // stable across builds, so that checkpoints can only be resumed
+    // against the problem they were taken from.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(self.columns() as u64);
+        hash.write(self.num_secondary_columns as u64);
+        hash.write(self.rows() as u64);
+        for row in self.ordered_coloured_rows() {
+            for (j, colour) in row {
+                hash.write(j as u64);
+                hash.write(colour as u64);
+            }
+            hash.write(u64::MAX);
+        }
+        for &(lo, hi) in &self.multiplicities {
+            hash.write(lo as u64);
+            hash.write(hi as u64);
+        }
+        for &cost in &self.costs {
+            hash.write(cost);
+        }
+        for g in &self.symmetries {
+            for &r in g {
+                hash.write(r as u64);
+            }
+        }
+        hash.finish()
+    }
 }
 
 /// A sparse binary matrix type. Stored in compressed sparse row
@@ -98,6 +394,9 @@ pub struct SparseBinaryMatrix {
     num_cols: usize,
     cols: Vec<usize>,
     row_starts: Vec<usize>,
+    // The colour of each 1, in the same order as `cols`, or empty if
+    // the matrix is uncoloured.
+    colours: Vec<usize>,
 }
 
 impl SparseBinaryMatrix {
@@ -121,6 +420,7 @@ impl SparseBinaryMatrix {
             num_cols: COLUMNS,
             cols,
             row_starts,
+            colours: vec![],
         }
     }
 
@@ -130,15 +430,25 @@ impl SparseBinaryMatrix {
     pub fn from_sparse_rows(
         rows: impl Iterator<Item = impl Iterator<Item = usize>>,
         num_cols: usize
-    ) -> Result<Self, ()> {
+    ) -> Result<Self, ExactCoverProblemError> {
         let mut cols = vec![];
         let mut row_starts = vec![];
-        // TODO!! Check the rows are strictly increasing and validly
-        // col-indexed or this will go badly wrong.
-        // For now just trust the user doesn't fuck it up.
         row_starts.push(0);
-        for row in rows {
-            cols.extend(row);
+        for (row_idx, row) in rows.enumerate() {
+            let start = cols.len();
+            for col_idx in row {
+                if col_idx >= num_cols {
+                    return Err(ExactCoverProblemError::ColumnIndexOutOfBounds {
+                        row_idx, col_idx,
+                    });
+                }
+                if cols.len() > start && cols[cols.len()-1] >= col_idx {
+                    return Err(ExactCoverProblemError::ColumnIndicesOutOfOrder {
+                        row_idx,
+                    });
+                }
+                cols.push(col_idx);
+            }
             row_starts.push(cols.len());
         }
 
@@ -146,9 +456,37 @@ impl SparseBinaryMatrix {
             num_cols,
             cols,
             row_starts,
+            colours: vec![],
         })
     }
 
+    /// Creates a sparse binary matrix with colours from an iterator of
+    /// sparse array rows of `(column, colour)` pairs, where colour 0
+    /// means uncoloured.
+    /// Column indices must be strictly increasing for each row and valid.
+    pub fn from_coloured_sparse_rows(
+        rows: impl Iterator<Item = impl Iterator<Item = (usize, usize)>>,
+        num_cols: usize
+    ) -> Result<Self, ExactCoverProblemError> {
+        let rows = rows.map(|row| row.collect::<Vec<_>>()).collect::<Vec<_>>();
+        let mut matrix = Self::from_sparse_rows(
+            rows.iter().map(|row| row.iter().map(|&(col, _)| col)),
+            num_cols,
+        )?;
+        if rows.iter().flatten().any(|&(_, colour)| colour != 0) {
+            matrix.colours = rows.iter().flatten()
+                .map(|&(_, colour)| colour)
+                .collect();
+        }
+        Ok(matrix)
+    }
+
+    /// Whether any entry of the matrix is coloured.
+    pub fn is_coloured(&self) -> bool {
+        !self.colours.is_empty()
+    }
+
+    /// The number of rows of the matrix.
     pub fn num_rows(&self) -> usize {
         self.row_starts.len().checked_sub(1).unwrap()
     }
@@ -164,6 +502,22 @@ impl SparseBinaryMatrix {
                 (start..end).map(|i| self.cols[i])
             })
     }
+
+    /// Returns an iterator of rows, which are themselves iterators over
+    /// the `(column, colour)` pairs of the 1s of the array. The colour is
+    /// 0 for uncoloured entries.
+    pub fn ordered_coloured_rows(&self)
+        -> impl Iterator<Item = impl Iterator<Item = (usize, usize)> + '_>
+    {
+        (0..(self.row_starts.len()-1))
+            .map(|r| {
+                let start = self.row_starts[r];
+                let end = self.row_starts[r+1];
+                (start..end).map(|i| {
+                    (self.cols[i], self.colours.get(i).copied().unwrap_or(0))
+                })
+            })
+    }
 }
 
 #[cfg(test)]
@@ -187,6 +541,91 @@ mod test {
         assert_eq!(output, vec![vec![0,1],vec![1,3],vec![2,3,4],vec![5]]);
     }
 
+    #[test]
+    fn test_from_sparse_rows_rejects_invalid_rows() {
+        let out_of_order = SparseBinaryMatrix::from_sparse_rows(
+            [vec![0, 2], vec![2, 1]].into_iter().map(|r| r.into_iter()), 3);
+        assert!(matches!(out_of_order,
+            Err(ExactCoverProblemError::ColumnIndicesOutOfOrder { row_idx: 1 })));
+
+        let out_of_bounds = SparseBinaryMatrix::from_sparse_rows(
+            [vec![0, 3]].into_iter().map(|r| r.into_iter()), 3);
+        assert!(matches!(out_of_bounds,
+            Err(ExactCoverProblemError::ColumnIndexOutOfBounds {
+                row_idx: 0, col_idx: 3,
+            })));
+    }
+
+    #[test]
+    fn test_coloured_rows() {
+        let problem = ExactCoverProblem::new_coloured(
+            [vec![(0, 0), (2, 5)], vec![(1, 0)]].into_iter().map(|r| r.into_iter()),
+            3, 1).unwrap();
+        assert!(problem.is_coloured());
+        let output = problem.ordered_coloured_rows()
+            .map(|row| row.collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(output, vec![vec![(0, 0), (2, 5)], vec![(1, 0)]]);
+
+        let coloured_primary = ExactCoverProblem::new_coloured(
+            [vec![(0, 0), (1, 3)]].into_iter().map(|r| r.into_iter()), 3, 1);
+        assert!(matches!(coloured_primary,
+            Err(ExactCoverProblemError::ColouredPrimaryColumn {
+                row_idx: 0, col_idx: 1,
+            })));
+
+        // All colours 0 is just an uncoloured problem.
+        let uncoloured = ExactCoverProblem::new_coloured(
+            [vec![(0, 0)]].into_iter().map(|r| r.into_iter()), 1, 0).unwrap();
+        assert!(!uncoloured.is_coloured());
+    }
+
+    #[test]
+    fn test_multiplicities() {
+        let mut problem = ExactCoverProblem::new(
+            [vec![0, 1], vec![2]].into_iter().map(|r| r.into_iter()), 3, 1)
+            .unwrap();
+        assert!(matches!(problem.set_multiplicities(vec![(1, 1)]),
+            Err(ExactCoverProblemError::WrongNumberOfMultiplicities)));
+        assert!(matches!(problem.set_multiplicities(vec![(1, 1), (2, 1)]),
+            Err(ExactCoverProblemError::InvalidMultiplicity { col_idx: 1 })));
+        assert!(matches!(problem.set_multiplicities(vec![(0, 0), (1, 1)]),
+            Err(ExactCoverProblemError::InvalidMultiplicity { col_idx: 0 })));
+
+        problem.set_multiplicities(vec![(1, 1), (1, 1)]).unwrap();
+        assert!(!problem.has_multiplicities());
+        problem.set_multiplicities(vec![(0, 2), (1, 1)]).unwrap();
+        assert!(problem.has_multiplicities());
+        assert_eq!(problem.multiplicity(0), (0, 2));
+        assert_eq!(problem.multiplicity(1), (1, 1));
+        assert_eq!(problem.multiplicity(2), (0, 1));
+    }
+
+    #[test]
+    fn test_costs() {
+        let mut problem = ExactCoverProblem::new(
+            [vec![0], vec![0], vec![]].into_iter().map(|r| r.into_iter()), 1, 0)
+            .unwrap();
+        assert!(matches!(problem.set_costs(vec![1, 2]),
+            Err(ExactCoverProblemError::WrongNumberOfCosts)));
+        problem.set_costs(vec![0, 0, 0]).unwrap();
+        assert!(!problem.has_costs());
+        problem.set_costs(vec![3, 5, 7]).unwrap();
+        assert!(problem.has_costs());
+        assert_eq!(problem.cost(1), 5);
+        assert_eq!(problem.solution_cost(&ExactCover(vec![1, 2])), 12);
+    }
+
+    #[test]
+    fn test_too_many_secondary_columns() {
+        let rows = [vec![], vec![], vec![]].into_iter().map(|r| r.into_iter());
+        assert!(matches!(ExactCoverProblem::new(rows, 0, 3),
+            Err(ExactCoverProblemError::TooManySecondaryColumns)));
+        let matrix = SparseBinaryMatrix::from_array_2d::<3, 0>([[],[],[]]);
+        assert!(matches!(ExactCoverProblem::new_general(matrix, 3),
+            Err(ExactCoverProblemError::TooManySecondaryColumns)));
+    }
+
     // #[test]
     // fn test_ordered_points() {
     //     let o = false; let x = true;
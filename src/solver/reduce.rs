@@ -0,0 +1,283 @@
+//! Shrinking a problem before solving it.
+//!
+//! The reductions are the classic ones, applied until none of them
+//! changes anything:
+//!
+//! - A primary column with a single row forces that row into every
+//!   solution, so it is taken out along with its columns and every row
+//!   clashing with it.
+//! - A row clashing with every row of some primary column can never be
+//!   chosen, as nothing would be left to cover that column.
+//! - If every row of a primary column `c` has a 1 in an uncoloured column
+//!   `d`, the row covering `c` also covers `d`, so the other rows of `d`
+//!   can never be chosen. Then `d` has exactly the rows of `c`, and is
+//!   covered whenever `c` is, so it can be dropped. This includes
+//!   duplicate columns.
+//!
+//! Each reduction keeps exactly the solutions of the original problem,
+//! once the forced rows are added back.
+
+use super::{ExactCover, ExactCoverProblem};
+
+/// The relationship between a problem and its reduction by
+/// [`ExactCoverProblem::reduce`], through which solutions of the reduced
+/// problem are lifted back to solutions of the original.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reduction {
+    // The original index of each row and column of the reduced problem.
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+    // The rows in every solution, in increasing order.
+    forced: Vec<usize>,
+}
+
+impl Reduction {
+    /// The index in the original problem of a row of the reduced problem.
+    pub fn original_row(&self, row: usize) -> usize {
+        self.rows[row]
+    }
+
+    /// The index in the original problem of a column of the reduced
+    /// problem.
+    pub fn original_column(&self, col: usize) -> usize {
+        self.columns[col]
+    }
+
+    /// The rows of the original problem found to be in every solution,
+    /// in increasing order.
+    pub fn forced_rows(&self) -> &[usize] {
+        &self.forced
+    }
+
+    /// Turns a solution of the reduced problem into the corresponding
+    /// solution of the original problem, with its rows in increasing
+    /// order.
+    pub fn lift(&self, solution: &ExactCover) -> ExactCover {
+        let mut rows = solution.0.iter()
+            .map(|&r| self.rows[r])
+            .chain(self.forced.iter().copied())
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+        ExactCover(rows)
+    }
+}
+
+/// The state of a problem part way through being reduced.
+struct Reducer<'a> {
+    problem: &'a ExactCoverProblem,
+    // The `(column, colour)` entries of each row.
+    rows: Vec<Vec<(usize, usize)>>,
+    row_alive: Vec<bool>,
+    col_alive: Vec<bool>,
+    // The live rows of each column, as of the start of the pass.
+    col_rows: Vec<Vec<usize>>,
+    forced: Vec<usize>,
+}
+
+pub(crate) fn reduce(problem: &ExactCoverProblem) -> (ExactCoverProblem, Reduction) {
+    let mut x = Reducer {
+        problem,
+        rows: problem.ordered_coloured_rows()
+            .map(|row| row.collect())
+            .collect(),
+        row_alive: vec![true; problem.rows()],
+        col_alive: vec![true; problem.columns()],
+        col_rows: vec![],
+        forced: vec![],
+    };
+    // Forcing rows is only sound when each column is covered once.
+    if !problem.has_multiplicities() {
+        while x.pass() {}
+    }
+    x.finish()
+}
+
+impl Reducer<'_> {
+    // Applies one kind of reduction wherever it can, returning whether
+    // anything changed. Returns false once a primary column has no rows,
+    // as the problem then has no solutions anyway.
+    fn pass(&mut self) -> bool {
+        self.col_rows = vec![vec![]; self.col_alive.len()];
+        for (r, row) in self.rows.iter().enumerate() {
+            if self.row_alive[r] {
+                for &(j, _) in row {
+                    self.col_rows[j].push(r);
+                }
+            }
+        }
+        let primary = self.problem.primary_columns();
+        if (0..primary).any(|c| self.col_alive[c] && self.col_rows[c].is_empty()) {
+            return false;
+        }
+        self.force_rows() || self.drop_subset_columns() || self.drop_hopeless_rows()
+    }
+
+    fn force_rows(&mut self) -> bool {
+        let mut changed = false;
+        for c in 0..self.problem.primary_columns() {
+            if !self.col_alive[c] {
+                continue;
+            }
+            let rows = self.live_rows(c);
+            if let [f] = rows[..] {
+                self.forced.push(f);
+                let clashing = self.rows[f].iter()
+                    .flat_map(|&(j, _)| &self.col_rows[j])
+                    .copied()
+                    .filter(|&s| self.row_alive[s] && self.clash(f, s))
+                    .collect::<Vec<_>>();
+                for s in clashing {
+                    self.row_alive[s] = false;
+                }
+                // Columns given a colour by the forced row stay, for the
+                // rows agreeing with it.
+                for &(j, colour) in &self.rows[f] {
+                    if colour == 0 {
+                        self.col_alive[j] = false;
+                    }
+                }
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn drop_subset_columns(&mut self) -> bool {
+        let mut changed = false;
+        for c in 0..self.problem.primary_columns() {
+            if !self.col_alive[c] {
+                continue;
+            }
+            let rows = self.live_rows(c);
+            let Some(&first) = rows.first() else { continue };
+            let candidates = self.rows[first].iter()
+                .map(|&(d, _)| d)
+                .filter(|&d| d != c && self.col_alive[d])
+                .collect::<Vec<_>>();
+            for d in candidates {
+                if !self.col_alive[d] {
+                    continue;
+                }
+                let others = self.live_rows(d);
+                let uncoloured = others.iter()
+                    .all(|&s| self.colour(s, d) == 0);
+                let superset = rows.iter()
+                    .all(|r| others.binary_search(r).is_ok());
+                if uncoloured && superset {
+                    for s in others {
+                        self.row_alive[s] = rows.binary_search(&s).is_ok();
+                    }
+                    self.col_alive[d] = false;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    fn drop_hopeless_rows(&mut self) -> bool {
+        let mut changed = false;
+        let mut clashes = vec![false; self.rows.len()];
+        for r in 0..self.rows.len() {
+            if !self.row_alive[r] {
+                continue;
+            }
+            let mut clashing = vec![];
+            for &(j, _) in &self.rows[r] {
+                for &s in &self.col_rows[j] {
+                    if self.row_alive[s] && !clashes[s] && self.clash(r, s) {
+                        clashes[s] = true;
+                        clashing.push(s);
+                    }
+                }
+            }
+            // Rows removed during this pass can't be chosen either.
+            let hopeless = (0..self.problem.primary_columns()).any(|c| {
+                let rows = &self.col_rows[c];
+                self.col_alive[c] && !rows.is_empty() && rows.iter()
+                    .all(|&s| s != r && (clashes[s] || !self.row_alive[s]))
+            });
+            for s in clashing {
+                clashes[s] = false;
+            }
+            if hopeless {
+                self.row_alive[r] = false;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // The live rows of a column, in increasing order.
+    fn live_rows(&self, c: usize) -> Vec<usize> {
+        self.col_rows[c].iter()
+            .copied()
+            .filter(|&r| self.row_alive[r])
+            .collect()
+    }
+
+    // The colour row r gives column j, which it must have a 1 in.
+    fn colour(&self, r: usize, j: usize) -> usize {
+        self.rows[r].iter()
+            .find(|&&(k, _)| k == j)
+            .map_or(0, |&(_, colour)| colour)
+    }
+
+    // Whether rows r and s can't both be in a solution.
+    fn clash(&self, r: usize, s: usize) -> bool {
+        r == s || self.rows[r].iter().any(|&(j, colour)| {
+            self.rows[s].iter().any(|&(k, other)| k == j
+                && (colour == 0 || colour != other))
+        })
+    }
+
+    // Builds the reduced problem from the live rows and columns.
+    fn finish(mut self) -> (ExactCoverProblem, Reduction) {
+        // Secondary columns without rows left are dropped too.
+        let primary = self.problem.primary_columns();
+        let mut used = vec![false; self.col_alive.len()];
+        for (r, row) in self.rows.iter().enumerate() {
+            if self.row_alive[r] {
+                for &(j, _) in row {
+                    used[j] = true;
+                }
+            }
+        }
+        for (alive, used) in self.col_alive.iter_mut().zip(used).skip(primary) {
+            *alive &= used;
+        }
+        let columns = (0..self.col_alive.len())
+            .filter(|&j| self.col_alive[j])
+            .collect::<Vec<_>>();
+        let mut new_index = vec![usize::MAX; self.col_alive.len()];
+        for (i, &j) in columns.iter().enumerate() {
+            new_index[j] = i;
+        }
+        let secondary = columns.iter().filter(|&&j| j >= primary).count();
+        let rows = (0..self.rows.len())
+            .filter(|&r| self.row_alive[r])
+            .collect::<Vec<_>>();
+        // A live row's dropped columns are covered by the forced rows, or
+        // whenever the columns that made them redundant are.
+        let mut reduced = ExactCoverProblem::new_coloured(
+            rows.iter().map(|&r| self.rows[r].iter()
+                .filter(|&&(j, _)| self.col_alive[j])
+                .map(|&(j, colour)| (new_index[j], colour))),
+            columns.len(),
+            secondary,
+        ).expect("reduced problem is well formed");
+        if self.problem.has_costs() {
+            let costs = rows.iter().map(|&r| self.problem.cost(r)).collect();
+            reduced.set_costs(costs).expect("one cost per row");
+        }
+        if self.problem.has_multiplicities() {
+            let multiplicities = (0..reduced.primary_columns())
+                .map(|c| self.problem.multiplicity(columns[c]))
+                .collect();
+            reduced.set_multiplicities(multiplicities)
+                .expect("multiplicities were valid");
+        }
+        self.forced.sort_unstable();
+        (reduced, Reduction { rows, columns, forced: self.forced })
+    }
+}
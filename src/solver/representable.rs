@@ -1,5 +1,5 @@
 use super::{
-    ExactCover, ExactCoverSolver, ExactCoverProblem, PartialCover
+    ExactCover, ExactCoverProblem, PartialCover, Symmetry
 };
 
 /// A trait for a problem representable as an exact cover problem.
@@ -23,11 +23,22 @@ pub trait ExactCoverRepresentable {
     /// Converts the problem into an exact cover problem.
     fn exact_cover_problem(&self) -> ExactCoverProblem;
 
+    /// Symmetries of the exact cover problem, which need only generate
+    /// its symmetry group. Passing them to
+    /// [`set_symmetries`](ExactCoverProblem::set_symmetries) makes the
+    /// solver report only one solution from each class of symmetric
+    /// solutions. The default is no symmetries.
+    fn symmetries(&self) -> Vec<Symmetry> {
+        vec![]
+    }
+
     /// Converts an exact cover into a domain-specific solution.
+    #[allow(clippy::wrong_self_convention)]
     fn from_exact_cover(&self, s: &ExactCover) -> Self::TSolution;
 
     /// Converts a partial cover into a domain-specific partial
     /// solution.
+    #[allow(clippy::wrong_self_convention)]
     fn from_partial_cover(
         &self, s: &PartialCover,
     ) -> Self::TPartialSolution;
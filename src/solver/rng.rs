@@ -0,0 +1,51 @@
+//! A small deterministic pseudorandom number generator.
+//!
+//! The solver only needs randomness for tie-breaking and sampling, but
+//! needs it to replay identically for a given seed on every platform and
+//! across versions, so we use our own SplitMix64 rather than an external
+//! crate whose streams may change.
+
+/// A SplitMix64 generator.
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly random integer in `0..n`. `n` must be nonzero.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        // Lemire's multiply-shift method, rejecting the biased zone.
+        let n = n as u64;
+        loop {
+            let m = (self.next_u64() as u128) * (n as u128);
+            let low = m as u64;
+            if low >= n.wrapping_neg() % n {
+                return (m >> 64) as usize;
+            }
+        }
+    }
+
+    /// A uniformly random integer in `0..n`. `n` must be nonzero.
+    pub(crate) fn below_u128(&mut self, n: u128) -> u128 {
+        // Reject draws from the incomplete copy of 0..n at the top.
+        let zone = u128::MAX - u128::MAX % n;
+        loop {
+            let x = (self.next_u64() as u128) << 64 | self.next_u64() as u128;
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
@@ -1,41 +1,51 @@
-// use bumpalo::{Bump};
-use arrayvec::ArrayVec;
-use itertools::Itertools;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::time::Instant;
 
 use super::{
-    output::PartialCover, ExactCover, ExactCoverProblem,
-    Solutions, SolverSteps, SolverStep
+    assumptions::{AssumptionError, Assumptions},
+    budget::SearchBudget,
+    checkpoint::{tag, CheckpointError, SavedState},
+    estimate::{Samples, TreeSizeEstimate},
+    handle::{Progress, ProgressLink, SolverHandle},
+    output::{PartialCover, SearchOutcome}, ExactCover, ExactCoverProblem,
+    Solutions, SolutionsByCost, SolverSteps, SolverStep,
+    chooser::{ColumnChooser, Mrv, MrvRandomTies},
+    links::{Branch, Links, IndexWidth},
+    rng::SplitMix64,
+    subtree::{SubtreeError, SubtreePath},
+    symmetry,
+    zdd::{SolutionZdd, ZddBuilder},
 };
-
-// TODO: change internal layout so we don't waste space
-// for size and row label for non-columns.
-// TODO: remove all allocations?
-// - The root node only uses left and right.
-// - Column nodes use LRUD, col points to themselves so unnecessary,
-// no row-label.
-// - Ordinary nodes use LRUD col row_label. No size.
-// The size of this entire structure is something like
-// 1 + numcolumns + numones + the stack + #emptyrows (bounded by rows?)
-// + the current working solution
-#[derive(Debug)]
-struct Node {
-    left: usize,
-    right: usize,
-    up: usize,
-    down: usize,
-    col: usize,
-    row_label: usize,
-    size: usize,
-}
+#[cfg(feature = "stats")]
+use super::stats::SolverStats;
 
 /// A state of the generator state machine.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum FinalState {
     Start,
     AfterColumnChoice { col_node: usize },
     AfterAddOrReplaceRow { r: usize },
     AfterRemoveRow { col_node: usize },
     Resume,
+    /// Reporting the current solution of `len` rows together with each
+    /// subset of the empty rows in turn. The current subset lives in
+    /// `empty_subset`.
+    NextEmptyRowSubset { len: usize },
+    /// All primary columns are covered. Considering the optional rows
+    /// from index `i` of `optional_rows` onwards.
+    Optional { i: usize },
+    AfterPushOptionalRow { i: usize },
+    ResumeOptional { i: usize },
+    /// The counterparts of the states above for problems with
+    /// multiplicities. The first row tried for the column at each level
+    /// lives in `ft`.
+    AfterMultiColumnChoice { col_node: usize },
+    AfterPushMultiRow,
+    ResumeMulti,
+    /// Trying the column no more times, so the level has no row.
+    ResumeMultiSkip,
+    AfterRemoveMultiRow { col_node: usize },
 }
 
 /// An exact cover solver.
@@ -50,180 +60,840 @@ enum FinalState {
 /// until the next discrete solver step, if there are any. Calls to these
 /// may be interleaved with no problem. The solver also exposes
 /// iterator wrapper interfaces via `.iter_solutions()` and `.iter_steps()`.
-#[derive(Debug)]
-pub struct ExactCoverSolver {
-    x: Vec<Node>,
+///
+/// The solver is generic over the [`ColumnChooser`] deciding which
+/// column to branch on at each step, which defaults to [`Mrv`].
+///
+/// Cloning a solver forks it at its current position: the clone carries
+/// on from exactly the same point independently of the original. Only
+/// the state of the search is copied; the parts of the solver that never
+/// change are shared. See [`fork`](Self::fork).
+#[derive(Debug, Clone)]
+pub struct ExactCoverSolver<C = Mrv> {
+    x: Links,
+    chooser: C,
     // Set of node items constituting the current solution.
     o: Vec<usize>,
     // Buffer into which to map row indices of the above for reporting.
     o_rows: Vec<usize>,
+    // The parts of the solver that never change, shared between forks.
+    topology: Arc<Topology>,
+    // Indices into `empty_rows` of the subset currently being reported,
+    // in increasing order. Subsets are enumerated by size, then
+    // lexicographically.
+    empty_subset: Vec<usize>,
+    // Whether to expand each solution with every subset of empty rows.
+    expand_empty_rows: bool,
+    // Whether reported solutions list their rows. Counting turns this off
+    // so as not to build a vector for every solution.
+    list_rows: bool,
+    // bounded by the most rows a solution can have
+    stack: Vec<FinalState>,
+    // The subtree the search is confined to.
+    subtree: SubtreePath,
+    // The rows assumed to be in or out of every solution.
+    assumptions: Assumptions,
+    // The states below this depth belong to the forced rows and the rows
+    // along the path to `subtree`, and are never popped.
+    floor: usize,
+    // For problems with multiplicities, the first row tried at each
+    // level, from which the rows tweaked at that level can be restored.
+    ft: Vec<usize>,
+    // The source of random row orders for randomised solvers.
+    rng: Option<SplitMix64>,
+    // Restart randomised searches after this many nodes without a
+    // solution.
+    restart_after: Option<u64>,
+    nodes_since_solution: u64,
+    // The number of nodes visited since the solver was created, which
+    // budgets are measured against.
+    nodes_visited: u64,
+    // The total cost of the rows in `o`.
+    cost: u64,
+    // Only solutions costing at most this much are searched for.
+    cost_limit: Option<u64>,
+    // The least cost of a partial solution or lower bound that went
+    // over `cost_limit`, i.e. the least cost of any solution pruned.
+    least_pruned_cost: Option<u64>,
+    // The progress shared with this solver's handles, if it has any.
+    progress: ProgressLink,
+    // Each step taken since the search last went back to its start,
+    // with how to undo it, if steps are being recorded. The search running
+    // out is recorded as `None`.
+    history: Option<Vec<(Option<SolverStep>, StepRecord)>>,
+    // The statistics gathered so far, with the mems spent before they
+    // were last reset in place of the mems spent since.
+    #[cfg(feature = "stats")]
+    stats: SolverStats,
+}
+
+/// The state of a solver before a step, apart from the stack below
+/// `kept`, which the step left alone.
+#[derive(Debug, Clone)]
+struct StepRecord {
+    kept: usize,
+    // The states above `kept`, as encoded by `encode_state`.
+    states: Vec<(u64, usize)>,
+    cost: u64,
+    nodes_since_solution: u64,
+    least_pruned_cost: Option<u64>,
+    empty_subset: Vec<usize>,
+}
+
+/// The parts of a solver determined entirely by its problem.
+#[derive(Debug)]
+struct Topology {
     /// Empty rows. The default behaviour of Algorithm X / Dancing Links
     /// entirely ignores empty rows. For each every solution S we need
     /// to add 2^S solutions, one for each subset of empty rows.
-    /// TODO: of course test this.
     empty_rows: Vec<usize>,
-    // bounded by num columns
-    stack: Vec<FinalState>,
+    /// Optional rows: the first node of each row whose 1s are all in
+    /// secondary columns, in row order. Algorithm X only ever branches
+    /// on primary columns, so these rows would never be chosen. Once
+    /// all primary columns are covered, we enumerate every subset of
+    /// these compatible with the current solution by including or
+    /// excluding each in turn.
+    optional_rows: Vec<usize>,
+    // The first node of each row by label, or 0 for empty rows.
+    row_nodes: Vec<usize>,
+    // The cost of each row by label, or empty if every row costs 0. The
+    // rows of every column are then sorted by cost.
+    row_costs: Vec<u64>,
+    // The row permutations of the problem's symmetry group, other than
+    // the identity. Only the least of the solutions they map to one
+    // another is reported.
+    symmetries: Vec<Vec<usize>>,
+    // The fingerprint of the problem, checked when resuming from a
+    // checkpoint.
+    fingerprint: u64,
 }
 
-// A generic value for unused values.
-const UNUSED: usize = usize::MAX;
 const HEAD: usize = 0;
 
-const MAX_COLS: usize = 4096;
-const MAX_ROWS: usize = 4096;
-const MAX_ONES: usize = 65536 - MAX_COLS;
+// How many steps a search with handles takes between updates of its
+// estimated fraction explored.
+const PROGRESS_INTERVAL: u64 = 1024;
 
-
-// Let's do some maths.
-// Currently we'll ignore the empty_rows set.
-// We have three constants: MAX_COLS, MAX_ROWS, and MAX_ONES.
-// (we ensure that MAX_ONES >= MAX_ROWS and MAX_ONES >= MAX_COLS)
-
-type ENTRY_IDX = u16;
-type COL_OR_ROW_IDX = u16;
+// How many steps a budgeted search takes between checks of the clock.
+const DEADLINE_CHECK_STEPS: u64 = 64;
 
 impl ExactCoverSolver {
-    pub fn memory_reqs(
-        num_cols: usize,
-        num_rows: usize,
-        num_ones: usize) -> usize {
-        // Since we are currently uing usizes, which are (probably?)
-        // 8 bytes. Let's make these explicitly u32s (u16 probably
-        // too small?) later.
-        let internal_ptr_size_bytes = 2;
-        // LRUD are pointers. Col, rowlabel and size are bounded
-        // by #cols+1, #rows and #rows respectively.
-        // For now just use the same pointer size.
-        let node_size_bytes = 7*internal_ptr_size_bytes;
-        // Notably does not depend on num rows
-        let node_arr_size = (1 + num_cols + num_ones)*node_size_bytes;
-        let solution_size_bound_bytes = internal_ptr_size_bytes
-            *num_rows;
-        // actually a col/row size bound, not a ptr bound
-        let stack_obj_size_bytes = internal_ptr_size_bytes;
-        let stack_size_bound_bytes = stack_obj_size_bytes*num_rows;
-        node_arr_size + solution_size_bound_bytes + stack_size_bound_bytes
-        // Let's ignore the empty rows for now.
-    }
-
-    /// Creates a new exact cover solver from a problem specification.
+    /// Creates a new exact cover solver from a problem specification,
+    /// using the default column choice rule.
     pub fn new(problem: &ExactCoverProblem) -> Self {
-        let primary_cols = problem.primary_columns();
-        let secondary_cols = problem.secondary_columns();
-        let ones = problem.matrix.ordered_points_rows();
-        let num_cols = primary_cols + secondary_cols;
-
-        // The root node lives at index 0 of the node list.
-        // If there are any columns, right should point at the first one.
-        // Not sure what happens if no columns. TODO check.
-        let root = Node {
-            left: primary_cols,
-            right: if num_cols > 0 { 1 } else { 0 },
-            up: UNUSED, down: UNUSED,
-            col: UNUSED, size: UNUSED, row_label: UNUSED,
-        };
-        let mut nodes = vec![root];
-
-        // The column headers live at indices 1 to
-        // num_cols of the node list. Head nodes above num_primary_cols
-        // are secondary columns. Primary col headers point left and right; secondary
-        // col headers point left and right to themselves.
-        // All col headers point up and down to themselves for now and set size to 0.
-        for c in 0..num_cols {
-            let col_header = Node {
-                left: if c < primary_cols { c } else { c+1 },
-                right: if c < primary_cols { c+2 } else { c+1 },
-                up: c+1,
-                down: c+1,
-                col: c+1,
-                row_label: UNUSED,
-                size: 0,
-            };
-            nodes.push(col_header);
+        Self::with_chooser(problem, Mrv)
+    }
+
+    /// The number of bytes of memory a new solver for the problem uses,
+    /// as reported by [`memory_footprint`](Self::memory_footprint), worked
+    /// out without building one. The search never needs more, except for
+    /// any steps recorded for [`prev_step`](Self::prev_step). The column
+    /// chooser's own memory isn't counted.
+    pub fn memory_reqs(problem: &ExactCoverProblem) -> usize {
+        let (mut empty_rows, mut optional_rows) = (0, 0);
+        for (len, all_secondary) in row_shapes(problem) {
+            if len == 0 {
+                empty_rows += 1;
+            } else if all_secondary {
+                optional_rows += 1;
+            }
+        }
+        let max_rows = max_solution_rows(problem, optional_rows);
+        // The solution buffers, the empty rows and the subset of them
+        // being reported, the optional rows and the first node of every
+        // row, as well as the first row tried at each level for problems
+        // with multiplicities.
+        let mut usizes = 2*max_rows + 2*empty_rows + optional_rows + problem.rows();
+        if problem.has_multiplicities() {
+            usizes += max_rows;
         }
+        let costs = match problem.has_costs() {
+            true => problem.rows(),
+            false => 0,
+        };
+        // Each row permutation of the symmetry group.
+        usizes += problem.symmetry_group().len()*problem.rows();
+        Links::bytes_for(problem, problem.rows() - empty_rows)
+            + size_of_val(problem.symmetry_group())
+            + usizes*size_of::<usize>()
+            + costs*size_of::<u64>()
+            + stack_capacity(max_rows)*size_of::<FinalState>()
+    }
 
-        // The last primary column's right wraps around to head.
-        nodes[primary_cols].right = HEAD;
+    /// Rebuilds a solver from a checkpoint taken by
+    /// [`checkpoint`](Self::checkpoint) from a solver for the same
+    /// problem, using the default column choice rule.
+    pub fn resume(
+        problem: &ExactCoverProblem, checkpoint: &[u8],
+    ) -> Result<Self, CheckpointError> {
+        Self::resume_with_chooser(problem, Mrv, checkpoint)
+    }
+}
 
-        let mut empty_rows = vec![];
-        for (i, row) in ones.enumerate() {
-            let mut first_of_row = None;
-
-            for j in row {
-                let new_index = nodes.len();
-
-                let left; let right;
-                match first_of_row {
-                    None => {
-                        first_of_row = Some(new_index);
-                        left = new_index;
-                        right = new_index;
-                    },
-                    Some(f) => {
-                        left = nodes[f].left;
-                        right = f;
-                    }
+impl ExactCoverSolver<MrvRandomTies> {
+    /// Creates a solver that searches in a random order. The rows within
+    /// each column are shuffled, and ties between columns with the fewest
+    /// rows are broken at random. The same seed always gives the same
+    /// sequence of steps.
+    pub fn new_randomised(problem: &ExactCoverProblem, seed: u64) -> Self {
+        let mut rng = SplitMix64::new(seed);
+        let chooser = MrvRandomTies::new(rng.next_u64());
+        let mut solver = Self::with_chooser(problem, chooser);
+        solver.x.shuffle_columns(&mut rng);
+        solver.rng = Some(rng);
+        solver.sort_by_cost();
+        solver
+    }
+
+    /// Sets the solver to restart its search from the top with a fresh
+    /// random order whenever it has explored `nodes` nodes of the search
+    /// tree (i.e. pushed or advanced that many rows) since the last
+    /// solution or restart. Each restart is reported as
+    /// `SolverStep::Restart`. This is useful for sampling solutions of
+    /// problems where an unlucky early choice leads to a huge fruitless
+    /// subtree, but note that solutions may be reported more than once
+    /// and the search only ends if it completes within a single budget.
+    /// Pass `None` to turn restarts off again.
+    pub fn set_restart_after(&mut self, nodes: Option<u64>) {
+        self.restart_after = nodes;
+    }
+}
+
+// The number of 1s in each row of a problem, and whether they are all in
+// secondary columns.
+fn row_shapes(problem: &ExactCoverProblem) -> impl Iterator<Item = (usize, bool)> + '_ {
+    let primary_cols = problem.primary_columns();
+    problem.ordered_points_rows().map(move |row| row.fold(
+        (0, true), |(len, all_secondary), j| (len + 1, all_secondary && j >= primary_cols)))
+}
+
+// The most rows a solution of a problem with the given number of
+// optional rows can have. Every row of a solution covers at least one
+// column, unless colours let several optional rows share their columns
+// or multiplicities let several rows share a primary column. In the
+// latter case a level may also decide to skip a column.
+fn max_solution_rows(problem: &ExactCoverProblem, optional_rows: usize) -> usize {
+    let primary_cols = problem.primary_columns();
+    let primary_levels = match problem.has_multiplicities() {
+        true => {
+            let mut lens = vec![0; primary_cols];
+            for j in problem.ordered_points_rows().flatten() {
+                if j < primary_cols {
+                    lens[j] += 1;
                 }
+            }
+            (0..primary_cols)
+                .map(|j| problem.multiplicity(j).1.min(lens[j]) + 1)
+                .sum()
+        },
+        false => primary_cols,
+    };
+    primary_levels + match problem.is_coloured() {
+        true => optional_rows,
+        false => problem.secondary_columns(),
+    }
+}
 
-                let col = j+1;
-                let down = col;
-                let up = nodes[col].up;
-                let row_label = i;
+// One state per row of the current solution, plus at most two more
+// while moving between levels.
+fn stack_capacity(max_rows: usize) -> usize {
+    max_rows + 2
+}
 
-                let new_node = Node {
-                    left, right, up, down, col, row_label, size: UNUSED,
-                };
-                nodes.push(new_node);
+impl<C: ColumnChooser> ExactCoverSolver<C> {
+    /// The number of bytes of memory this solver is actually using.
+    pub fn memory_footprint(&self) -> usize {
+        let usizes = self.o.capacity() + self.o_rows.capacity()
+            + self.topology.empty_rows.capacity() + self.empty_subset.capacity()
+            + self.topology.optional_rows.capacity() + self.ft.capacity()
+            + self.topology.row_nodes.capacity()
+            + self.topology.symmetries.iter().map(Vec::capacity).sum::<usize>();
+        self.x.memory_footprint()
+            + self.topology.symmetries.capacity()*size_of::<Vec<usize>>()
+            + usizes*size_of::<usize>()
+            + self.topology.row_costs.capacity()*size_of::<u64>()
+            + self.stack.capacity()*size_of::<FinalState>()
+    }
 
-                nodes[left].right = new_index;
-                nodes[first_of_row.unwrap()].left = new_index;
-                let last_of_col = nodes[col].up;
-                nodes[last_of_col].down = new_index;
-                nodes[col].up = new_index;
+    /// The width of the indices used by this solver's internal links,
+    /// chosen to be as narrow as the problem allows.
+    pub fn index_width(&self) -> IndexWidth {
+        self.x.width()
+    }
 
-                nodes[j+1].size += 1;
-            }
+    /// Creates a new exact cover solver from a problem specification,
+    /// choosing columns to branch on with the given chooser.
+    pub fn with_chooser(problem: &ExactCoverProblem, chooser: C) -> Self {
+        let num_cols = problem.columns();
 
-            if let None = first_of_row {
+        // Rows are laid out in order after the column headers and an
+        // initial spacer, each followed by a spacer of its own. Empty rows
+        // take up no nodes at all.
+        let mut empty_rows = vec![];
+        let mut optional_rows = vec![];
+        let mut row_nodes = Vec::with_capacity(problem.rows());
+        let mut first_of_row = num_cols + 2;
+        for (i, (len, all_secondary)) in row_shapes(problem).enumerate() {
+            if len == 0 {
                 empty_rows.push(i);
+                row_nodes.push(0);
+            } else {
+                row_nodes.push(first_of_row);
+                if all_secondary {
+                    optional_rows.push(first_of_row);
+                }
+                first_of_row += len + 1;
             }
         }
+        empty_rows.shrink_to_fit();
+        optional_rows.shrink_to_fit();
+        let max_rows = max_solution_rows(problem, optional_rows.len());
+
+        let row_costs = match problem.has_costs() {
+            true => (0..problem.rows()).map(|r| problem.cost(r)).collect(),
+            false => vec![],
+        };
 
+        let empty_rows_len = empty_rows.len();
+        let nonempty_rows = problem.rows() - empty_rows_len;
+        let mut x = Links::new(problem, nonempty_rows);
+        if !row_costs.is_empty() {
+            x.sort_columns_by_cost(&row_costs);
+        }
+        let mut chooser = chooser;
+        x.init_chooser(&mut chooser);
         Self {
-            x: nodes,
-            o: vec![0; num_cols],
-            o_rows: vec![0; num_cols],
-            empty_rows,
+            x,
+            chooser,
+            o: vec![0; max_rows],
+            o_rows: vec![0; max_rows],
+            topology: Arc::new(Topology {
+                empty_rows,
+                optional_rows,
+                row_nodes,
+                row_costs,
+                symmetries: problem.symmetry_group().to_vec(),
+                fingerprint: problem.fingerprint(),
+            }),
+            empty_subset: Vec::with_capacity(empty_rows_len),
+            expand_empty_rows: true,
+            list_rows: true,
             stack: {
-                let mut s = Vec::with_capacity(num_cols);
+                let mut s = Vec::with_capacity(stack_capacity(max_rows));
                 s.push(FinalState::Start);
                 s
+            },
+            subtree: SubtreePath::root(),
+            assumptions: Assumptions::default(),
+            floor: 0,
+            ft: match problem.has_multiplicities() {
+                true => vec![0; max_rows],
+                false => vec![],
+            },
+            rng: None,
+            restart_after: None,
+            nodes_since_solution: 0,
+            nodes_visited: 0,
+            cost: 0,
+            cost_limit: None,
+            least_pruned_cost: None,
+            progress: ProgressLink::default(),
+            history: None,
+            #[cfg(feature = "stats")]
+            stats: SolverStats::default(),
+        }
+    }
+
+    /// Forks the search at its current position, returning a solver that
+    /// carries on from exactly the same point independently of this one,
+    /// for exploring one branch of the search without disturbing the
+    /// other. The state of the links and the stack of choices are copied,
+    /// while everything determined by the problem alone is shared. The
+    /// fork has no [handles](Self::handle) of its own yet.
+    pub fn fork(&self) -> Self where C: Clone {
+        self.clone()
+    }
+
+    /// Rebuilds a solver from a checkpoint taken by
+    /// [`checkpoint`](Self::checkpoint) from a solver for the same
+    /// problem, choosing columns with the given chooser, which should be
+    /// a fresh one of the same kind as the original solver's. The new
+    /// solver carries on exactly where the original was.
+    pub fn resume_with_chooser(
+        problem: &ExactCoverProblem, chooser: C, checkpoint: &[u8],
+    ) -> Result<Self, CheckpointError> {
+        let mut solver = Self::with_chooser(problem, chooser);
+        solver.restore(checkpoint)?;
+        Ok(solver)
+    }
+
+    /// Saves the state of the search as compact bytes, from which
+    /// [`resume_with_chooser`](Self::resume_with_chooser) can rebuild the
+    /// solver against the same problem, even in another process. The
+    /// state is just the stack of choices the search has made, so its
+    /// size is proportional to the depth of the search rather than the
+    /// size of the problem. The cost bound, subtree and whether to expand
+    /// empty rows are saved too. A chooser's own state is not, beyond
+    /// what it learns from the columns being covered and uncovered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the solver is randomised, as its row order isn't saved.
+    pub fn checkpoint(&self) -> Vec<u8> {
+        assert!(self.rng.is_none(), "randomised solvers can't be checkpointed");
+        SavedState {
+            expand_empty_rows: self.expand_empty_rows,
+            cost_limit: self.cost_limit,
+            least_pruned_cost: self.least_pruned_cost,
+            cost: self.cost,
+            nodes_since_solution: self.nodes_since_solution,
+            subtree: self.subtree.choices().to_vec(),
+            forced: self.assumptions.forced.clone(),
+            forbidden: self.assumptions.forbidden.clone(),
+            floor: self.floor,
+            stack: self.stack.iter()
+                .enumerate()
+                .map(|(k, st)| self.encode_state(k, st))
+                .collect(),
+            empty_subset: self.empty_subset.clone(),
+        }.write(self.topology.fingerprint)
+    }
+
+    // The state at level k of the stack as a tag and a value, from which
+    // `replay_state` can redo it.
+    fn encode_state(&self, k: usize, st: &FinalState) -> (u64, usize) {
+        match *st {
+            FinalState::Start => (tag::START, 0),
+            FinalState::AfterColumnChoice { col_node } => (tag::AFTER_COLUMN_CHOICE, col_node),
+            FinalState::AfterAddOrReplaceRow { r } => (tag::AFTER_ADD_OR_REPLACE_ROW, r),
+            FinalState::AfterRemoveRow { col_node } => (tag::AFTER_REMOVE_ROW, col_node),
+            FinalState::Resume => (tag::RESUME, self.o[k]),
+            FinalState::NextEmptyRowSubset { len } => (tag::NEXT_EMPTY_ROW_SUBSET, len),
+            FinalState::Optional { i } => (tag::OPTIONAL, i),
+            FinalState::AfterPushOptionalRow { i } => (tag::AFTER_PUSH_OPTIONAL_ROW, i),
+            FinalState::ResumeOptional { i } => (tag::RESUME_OPTIONAL, i),
+            FinalState::AfterMultiColumnChoice { col_node } => {
+                (tag::AFTER_MULTI_COLUMN_CHOICE, col_node)
+            },
+            FinalState::AfterPushMultiRow => (tag::AFTER_PUSH_MULTI_ROW, self.o[k]),
+            FinalState::ResumeMulti => (tag::RESUME_MULTI, self.o[k]),
+            FinalState::ResumeMultiSkip => (tag::RESUME_MULTI_SKIP, self.o[k]),
+            FinalState::AfterRemoveMultiRow { col_node } => {
+                (tag::AFTER_REMOVE_MULTI_ROW, col_node)
+            },
+        }
+    }
+
+    // Sets a fresh solver to the state saved in a checkpoint, by redoing
+    // the covers implied by each saved state from the bottom of the stack
+    // up, just as the search did. The solver is left in an arbitrary
+    // state if this fails.
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), CheckpointError> {
+        let saved = SavedState::read(checkpoint, self.topology.fingerprint)?;
+        if saved.stack.len() > stack_capacity(self.o.len())
+            || saved.floor > saved.stack.len()
+            || saved.empty_subset.iter().any(|&i| i >= self.topology.empty_rows.len())
+        {
+            return Err(CheckpointError::Malformed);
+        }
+        self.assumptions = Assumptions::new(&saved.forced, &saved.forbidden,
+            &self.topology.row_nodes, self.x.has_multiplicities(),
+            &self.topology.symmetries)
+            .map_err(|_| CheckpointError::Malformed)?;
+        self.hide_assumed_rows();
+
+        self.stack.clear();
+        for (k, &(state_tag, value)) in saved.stack.iter().enumerate() {
+            let st = self.replay_state(k, state_tag, value)
+                .ok_or(CheckpointError::Malformed)?;
+            self.stack.push(st);
+        }
+        // The levels below the floor pin the forced rows in order and
+        // then the path to the subtree, and the cost is that of the rows
+        // on the stack, so both must agree with what was saved.
+        let subtree = self.pinned_subtree(saved.floor)
+            .filter(|path| path.choices() == saved.subtree)
+            .ok_or(CheckpointError::Malformed)?;
+        if self.stack_cost() != saved.cost {
+            return Err(CheckpointError::Malformed);
+        }
+
+        self.expand_empty_rows = saved.expand_empty_rows;
+        self.cost_limit = saved.cost_limit;
+        self.least_pruned_cost = saved.least_pruned_cost;
+        self.cost = saved.cost;
+        self.nodes_since_solution = saved.nodes_since_solution;
+        self.subtree = subtree;
+        self.floor = saved.floor;
+        self.empty_subset = saved.empty_subset;
+        Ok(())
+    }
+
+    // The path to the subtree pinned by the levels of the stack below
+    // `floor`, after those of the forced rows, or `None` if they aren't
+    // pinned rows or don't start with the forced rows.
+    fn pinned_subtree(&self, floor: usize) -> Option<SubtreePath> {
+        let forced = &self.assumptions.forced;
+        if floor < forced.len() {
+            return None;
+        }
+        let mut path = SubtreePath::root();
+        for (k, st) in self.stack[..floor].iter().enumerate() {
+            let r = self.o[k];
+            match (st, forced.get(k)) {
+                (FinalState::Resume | FinalState::ResumeOptional { .. }, Some(&row)) => {
+                    (self.x.row_label(r) == row).then_some(())?;
+                },
+                (FinalState::Resume, None) => {
+                    path.push(self.x.col(r) - 1, self.x.row_label(r));
+                },
+                _ => return None,
             }
         }
+        Some(path)
     }
 
-    // fn map_solution(&mut self, k: usize) -> &[usize] {
-    //     for i in 0..k {
-    //         let node = self.o[i];
-    //         let row = self.x[node].row_label;
-    //         self.o_rows[i] = row;
-    //     }
-    //     &self.o_rows[..k]
-    // }
+    // The total cost of the rows on the stack.
+    fn stack_cost(&self) -> u64 {
+        self.stack.iter()
+            .enumerate()
+            .filter(|(_, st)| matches!(st,
+                FinalState::AfterAddOrReplaceRow { .. }
+                | FinalState::Resume
+                | FinalState::AfterPushOptionalRow { .. }
+                | FinalState::ResumeOptional { .. }
+                | FinalState::AfterPushMultiRow
+                | FinalState::ResumeMulti))
+            .map(|(k, _)| self.row_cost(self.o[k]))
+            .sum()
+    }
+
+    // Redoes what the search did on the way to pushing the saved state
+    // with the given tag and value at depth k of the stack, returning
+    // the state, or `None` if it makes no sense. Columns must still be
+    // active and rows still linked in, as the search left them.
+    fn replay_state(&mut self, k: usize, state_tag: u64, value: usize) -> Option<FinalState> {
+        let multi = self.x.has_multiplicities();
+        let column = |x: &Links| {
+            (x.is_primary_column(value) && x.is_active(value)).then_some(value)
+        };
+        let row = |x: &Links| (x.is_entry(value) && !x.is_hidden(value)).then_some(value);
+        // Only these states don't fill in a level of the solution.
+        let fills_level = !matches!(state_tag,
+            tag::START | tag::NEXT_EMPTY_ROW_SUBSET | tag::OPTIONAL);
+        if fills_level && k >= self.o.len() {
+            return None;
+        }
+        let st = match state_tag {
+            tag::START => FinalState::Start,
+            tag::AFTER_COLUMN_CHOICE | tag::AFTER_REMOVE_ROW if !multi => {
+                let col_node = column(&self.x)?;
+                self.x.cover(col_node, &mut self.chooser);
+                match state_tag {
+                    tag::AFTER_COLUMN_CHOICE => FinalState::AfterColumnChoice { col_node },
+                    _ => FinalState::AfterRemoveRow { col_node },
+                }
+            },
+            tag::AFTER_ADD_OR_REPLACE_ROW | tag::RESUME if !multi => {
+                let r = row(&self.x)?;
+                let c = self.x.col(r);
+                (self.x.is_primary_column(c) && self.x.is_active(c)).then_some(())?;
+                self.x.cover(c, &mut self.chooser);
+                self.o[k] = r;
+                match state_tag {
+                    tag::AFTER_ADD_OR_REPLACE_ROW => FinalState::AfterAddOrReplaceRow { r },
+                    _ => {
+                        self.x.cover_others(r, &mut self.chooser);
+                        FinalState::Resume
+                    },
+                }
+            },
+            tag::NEXT_EMPTY_ROW_SUBSET => {
+                (self.map_solution(k) == value).then_some(())?;
+                FinalState::NextEmptyRowSubset { len: value }
+            },
+            tag::OPTIONAL if value <= self.topology.optional_rows.len() => {
+                FinalState::Optional { i: value }
+            },
+            tag::AFTER_PUSH_OPTIONAL_ROW | tag::RESUME_OPTIONAL
+                if value < self.topology.optional_rows.len() => {
+                let r = self.topology.optional_rows[value];
+                // Forced optional rows are hidden before they are pinned.
+                let forced = || self.assumptions.forced.contains(&self.x.row_label(r));
+                (!self.x.row_conflicts(r) && (!self.x.is_hidden(r) || forced()))
+                    .then_some(())?;
+                self.o[k] = r;
+                match state_tag {
+                    tag::AFTER_PUSH_OPTIONAL_ROW => FinalState::AfterPushOptionalRow { i: value },
+                    _ => {
+                        self.x.commit(r, &mut self.chooser);
+                        self.x.cover_others(r, &mut self.chooser);
+                        FinalState::ResumeOptional { i: value }
+                    },
+                }
+            },
+            tag::AFTER_MULTI_COLUMN_CHOICE if multi => {
+                let col_node = column(&self.x)?;
+                self.ft[k] = self.x.begin_branch(col_node, &mut self.chooser);
+                FinalState::AfterMultiColumnChoice { col_node }
+            },
+            tag::AFTER_PUSH_MULTI_ROW..=tag::AFTER_REMOVE_MULTI_ROW if multi => {
+                let (col_node, target) = match state_tag {
+                    tag::AFTER_PUSH_MULTI_ROW | tag::RESUME_MULTI => {
+                        let r = row(&self.x)?;
+                        (self.x.col(r), Branch::Row(r))
+                    },
+                    tag::RESUME_MULTI_SKIP => (column(&self.x)?, Branch::Skip),
+                    _ => (column(&self.x)?, Branch::Exhausted),
+                };
+                (self.x.is_primary_column(col_node) && self.x.is_active(col_node))
+                    .then_some(())?;
+                // Try the column's rows in turn until reaching the saved
+                // branch. Rows tried before it were covered and uncovered
+                // again, which makes no difference.
+                self.ft[k] = self.x.begin_branch(col_node, &mut self.chooser);
+                let mut x = self.ft[k];
+                loop {
+                    let branch = self.x.next_branch(col_node, x, &mut self.chooser);
+                    if branch == target {
+                        break;
+                    }
+                    match branch {
+                        Branch::Row(r) => x = self.x.down(r),
+                        _ => return None,
+                    }
+                }
+                match state_tag {
+                    tag::AFTER_PUSH_MULTI_ROW => {
+                        self.o[k] = value;
+                        FinalState::AfterPushMultiRow
+                    },
+                    tag::RESUME_MULTI => {
+                        self.o[k] = value;
+                        self.x.cover_others(value, &mut self.chooser);
+                        FinalState::ResumeMulti
+                    },
+                    tag::RESUME_MULTI_SKIP => {
+                        self.o[k] = col_node;
+                        FinalState::ResumeMultiSkip
+                    },
+                    _ => FinalState::AfterRemoveMultiRow { col_node },
+                }
+            },
+            _ => return None,
+        };
+        Some(st)
+    }
+
+    /// Restricts the search to solutions whose rows cost at most `bound`
+    /// in total, or lifts the restriction if `None`. Branches of the
+    /// search that can't lead to a solution within the bound are pruned.
+    pub fn set_cost_bound(&mut self, bound: Option<u64>) {
+        self.cost_limit = bound;
+    }
+
+    /// Finds a solution of least total cost by branch and bound, along
+    /// with its cost, or `None` if there are no (more) solutions within
+    /// the cost bound. Each solution found tightens the bound to just
+    /// below its cost, so this consumes the rest of the search.
+    pub fn min_cost_solution(&mut self) -> Option<(ExactCover, u64)> {
+        let mut best = None;
+        while let Some(solution) = self.next_solution() {
+            let cost = self.solution_cost(&solution);
+            best = Some((solution, cost));
+            match cost.checked_sub(1) {
+                Some(limit) => self.cost_limit = Some(limit),
+                None => break,
+            }
+        }
+        best
+    }
+
+    /// The total cost of the rows of a solution.
+    pub fn solution_cost(&self, solution: &ExactCover) -> u64 {
+        match self.topology.row_costs.is_empty() {
+            true => 0,
+            false => solution.0.iter().map(|&r| self.topology.row_costs[r]).sum(),
+        }
+    }
+
+    // The cost of the row containing node r.
+    fn row_cost(&self, r: usize) -> u64 {
+        match self.topology.row_costs.is_empty() {
+            true => 0,
+            false => self.topology.row_costs[self.x.row_label(r)],
+        }
+    }
+
+    // Whether a solution costing at least `cost` is within the cost
+    // limit, noting the cost if not.
+    fn check_limit(&mut self, cost: u64) -> bool {
+        match self.cost_limit {
+            Some(limit) if cost > limit => {
+                self.least_pruned_cost = Some(
+                    self.least_pruned_cost.map_or(cost, |c| c.min(cost)));
+                false
+            },
+            _ => true,
+        }
+    }
+
+    // Whether the row containing node r can be added to the current
+    // solution without exceeding the cost limit.
+    fn within_limit(&mut self, r: usize) -> bool {
+        self.cost_limit.is_none()
+            || self.check_limit(self.cost.saturating_add(self.row_cost(r)))
+    }
+
+    // Whether no solution extending the current one can be found within
+    // the cost limit.
+    fn exceeds_limit(&mut self) -> bool {
+        if self.cost_limit.is_none() || self.topology.row_costs.is_empty() {
+            return false;
+        }
+        let bound = self.x.cost_lower_bound(&self.topology.row_costs);
+        !self.check_limit(self.cost.saturating_add(bound))
+    }
+
+    // The total cost of the current subset of the empty rows.
+    fn empty_subset_cost(&self) -> u64 {
+        match self.topology.row_costs.is_empty() {
+            true => 0,
+            false => self.empty_subset.iter()
+                .map(|&i| self.topology.row_costs[self.topology.empty_rows[i]])
+                .sum(),
+        }
+    }
+
+    // Re-sorts the rows of every column by cost after shuffling them,
+    // keeping the random order among rows of equal cost.
+    fn sort_by_cost(&mut self) {
+        if !self.topology.row_costs.is_empty() {
+            self.x.sort_columns_by_cost(&self.topology.row_costs);
+        }
+    }
+
+    /// Sets whether each solution found by the search is expanded into
+    /// one solution per subset of the problem's empty rows. This is on by
+    /// default, so that every exact cover is reported. If turned off, only
+    /// the "core" solutions without any empty rows are reported, and the
+    /// full number of solutions is the number reported multiplied by
+    /// `empty_row_multiplier()`.
+    pub fn set_expand_empty_rows(&mut self, expand: bool) {
+        self.expand_empty_rows = expand;
+    }
+
+    /// The number of distinct solutions the problem's symmetries map a
+    /// solution to, counting itself. When the problem has symmetries only
+    /// one solution of each such class is reported, so the total number
+    /// of solutions is the sum of the orbit sizes of those reported. This
+    /// is 1 for a problem without symmetries.
+    pub fn orbit_size(&self, solution: &ExactCover) -> usize {
+        symmetry::orbit_size(&self.topology.symmetries, &solution.0)
+    }
+
+    /// The indices of the problem's rows with no 1s, in increasing order.
+    pub fn empty_rows(&self) -> &[usize] {
+        &self.topology.empty_rows
+    }
+
+    /// The number of exact covers each core solution expands into, i.e.
+    /// 2^n where n is the number of empty rows. Returns `None` if this
+    /// does not fit into a `u128`.
+    pub fn empty_row_multiplier(&self) -> Option<u128> {
+        u32::try_from(self.topology.empty_rows.len()).ok()
+            .and_then(|n| 1u128.checked_shl(n))
+    }
+
+    // Reports the current solution of k rows, first setting up the
+    // enumeration of its extensions by subsets of the empty rows.
+    fn report_solution(&mut self, k: usize) -> SolverStep {
+        self.nodes_since_solution = 0;
+        let len = self.map_solution(k);
+        if self.expand_empty_rows && !self.topology.empty_rows.is_empty() {
+            self.stack.push(FinalState::NextEmptyRowSubset { len });
+        }
+        SolverStep::ReportSolution(self.solution_with_empty_subset(len))
+    }
+
+    // Whether the row containing node r may be added to the current
+    // solution, i.e. none of its columns are covered or purified with
+    // another colour. Only used for optional rows, whose columns are all
+    // secondary.
+    fn is_compatible(&self, r: usize) -> bool {
+        !self.x.is_hidden(r) && !self.x.row_conflicts(r)
+    }
+
+    // Maps the nodes of the first k entries of the current solution
+    // to their row labels, skipping levels without a row. Returns the
+    // number of rows.
+    fn map_solution(&mut self, k: usize) -> usize {
+        let mut len = 0;
+        for i in 0..k {
+            let node = self.o[i];
+            if self.x.is_row_node(node) {
+                self.o_rows[len] = self.x.row_label(node);
+                len += 1;
+            }
+        }
+        len
+    }
+
+    // The solution made up of the first `len` mapped rows plus the
+    // current subset of the empty rows.
+    fn solution_with_empty_subset(&self, len: usize) -> ExactCover {
+        if !self.list_rows {
+            return ExactCover(vec![]);
+        }
+        let empty = self.empty_subset.iter().map(|&i| self.topology.empty_rows[i]);
+        ExactCover(self.o_rows[..len].iter().copied().chain(empty).collect())
+    }
+
+    // Advances `empty_subset` to the next subset of the empty rows,
+    // returning false if it has wrapped around past the full set.
+    fn advance_empty_subset(&mut self) -> bool {
+        let n = self.topology.empty_rows.len();
+        let len = self.empty_subset.len();
+        let s = &mut self.empty_subset;
+        match (0..len).rev().find(|&i| s[i] < n - len + i) {
+            Some(i) => {
+                s[i] += 1;
+                for j in i+1..len {
+                    s[j] = s[j-1] + 1;
+                }
+                true
+            },
+            None if len < n => {
+                s.clear();
+                s.extend(0..len+1);
+                true
+            },
+            None => {
+                s.clear();
+                false
+            },
+        }
+    }
 
     /// The current partial solution, i.e. the solver's current row stack.
     pub fn current_partial_solution(&self) -> PartialCover {
         let mut k = self.stack.len();
         match self.stack.last() {
-            Some(FinalState::AfterAddOrReplaceRow { .. } | FinalState::Resume) => (),
+            Some(FinalState::AfterAddOrReplaceRow { .. }
+                | FinalState::Resume
+                | FinalState::AfterPushOptionalRow { .. }
+                | FinalState::ResumeOptional { .. }
+                | FinalState::AfterPushMultiRow
+                | FinalState::ResumeMulti
+                | FinalState::ResumeMultiSkip) => (),
             _ => k = k.saturating_sub(1),
         }
 
         // TODO: replace with solution()
         PartialCover(self.o.iter()
             .take(k)
-            .map(|&r| self.x[r].row_label)
+            .filter(|&&r| self.x.is_row_node(r))
+            .map(|&r| self.x.row_label(r))
             .collect::<Vec<_>>())
     }
 
@@ -237,58 +907,757 @@ impl ExactCoverSolver {
         None
     }
 
+    // Counts the remaining solutions without listing their rows, unless
+    // they are needed to pass over solutions that aren't canonical.
+    pub(crate) fn count_solutions(&mut self) -> u128 {
+        self.list_rows = !self.topology.symmetries.is_empty();
+        let mut count = 0;
+        while let Some(step) = self.next_step() {
+            if let SolverStep::ReportSolution(_) = step {
+                count += 1;
+            }
+        }
+        self.list_rows = true;
+        count
+    }
+
+    /// Searches for the next solution like
+    /// [`next_solution`](Self::next_solution), but gives up once the
+    /// budget runs out, returning [`SearchOutcome::BudgetExhausted`]. The
+    /// search can then be carried on from where it stopped by calling
+    /// this again with a fresh budget, or by any other means. The budget
+    /// is reduced by the work done.
+    pub fn next_solution_within(&mut self, budget: &mut SearchBudget) -> SearchOutcome {
+        #[cfg(feature = "stats")]
+        let start_mems = self.x.mems();
+        let start_nodes = self.nodes_visited;
+        let mut steps = 0;
+        let outcome = loop {
+            let out_of_steps = budget.steps.is_some_and(|n| steps >= n);
+            let out_of_nodes = budget.nodes
+                .is_some_and(|n| self.nodes_visited - start_nodes >= n);
+            #[cfg(feature = "stats")]
+            let out_of_mems = budget.mems
+                .is_some_and(|n| self.x.mems() - start_mems >= n);
+            #[cfg(not(feature = "stats"))]
+            let out_of_mems = false;
+            let out_of_time = steps % DEADLINE_CHECK_STEPS == 0
+                && budget.deadline.is_some_and(|t| Instant::now() >= t);
+            if out_of_steps || out_of_nodes || out_of_mems || out_of_time
+                || budget.solutions == Some(0) {
+                break SearchOutcome::BudgetExhausted;
+            }
+            if self.is_cancelled() {
+                break SearchOutcome::Cancelled;
+            }
+            steps += 1;
+            match self.next_step() {
+                Some(SolverStep::ReportSolution(solution)) => {
+                    if let Some(n) = budget.solutions.as_mut() {
+                        *n -= 1;
+                    }
+                    break SearchOutcome::Solution(solution);
+                },
+                Some(_) => (),
+                None => break SearchOutcome::Finished,
+            }
+        };
+        if let Some(n) = budget.steps.as_mut() {
+            *n = n.saturating_sub(steps);
+        }
+        if let Some(n) = budget.nodes.as_mut() {
+            *n = n.saturating_sub(self.nodes_visited - start_nodes);
+        }
+        #[cfg(feature = "stats")]
+        if let Some(n) = budget.mems.as_mut() {
+            *n = n.saturating_sub(self.x.mems() - start_mems);
+        }
+        outcome
+    }
+
+    // Counts a node of the search tree as visited, i.e. a row pushed or
+    // advanced to.
+    fn visit_node(&mut self) {
+        self.nodes_since_solution += 1;
+        self.nodes_visited += 1;
+    }
+
+    /// The number of mems the solver has spent covering and uncovering
+    /// columns so far, as an estimate of the work it has done that is
+    /// independent of the machine. Following Knuth, a mem is roughly one
+    /// memory access. Only available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn mems(&self) -> u64 {
+        self.x.mems()
+    }
+
+    // The mems spent so far, or 0 without the `stats` feature, which
+    // compiles out counting them.
+    fn mems_spent(&self) -> u64 {
+        #[cfg(feature = "stats")]
+        return self.x.mems();
+        #[cfg(not(feature = "stats"))]
+        0
+    }
+
+    // Pops every state off the stack, undoing the covers each one
+    // implies, so that the links are back in their initial state.
+    fn unwind(&mut self) {
+        while let Some(st) = self.stack.pop() {
+            self.leave_state(self.stack.len(), st);
+        }
+        self.unhide_assumed_rows();
+        self.empty_subset.clear();
+        self.cost = 0;
+        self.floor = 0;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+    }
+
+    // Undoes the covers implied by the state just popped from level k of
+    // the stack.
+    fn leave_state(&mut self, k: usize, st: FinalState) {
+        match st {
+            FinalState::Start
+            | FinalState::NextEmptyRowSubset { .. }
+            | FinalState::Optional { .. }
+            | FinalState::AfterPushOptionalRow { .. } => (),
+            FinalState::AfterColumnChoice { col_node }
+            | FinalState::AfterRemoveRow { col_node } => {
+                self.x.uncover(col_node, &mut self.chooser);
+            },
+            FinalState::AfterAddOrReplaceRow { r } => {
+                self.x.uncover(self.x.col(r), &mut self.chooser);
+            },
+            FinalState::Resume => {
+                let r = self.o[k];
+                self.x.uncover_others(r, &mut self.chooser);
+                self.x.uncover(self.x.col(r), &mut self.chooser);
+            },
+            FinalState::ResumeOptional { i } => {
+                let r = self.topology.optional_rows[i];
+                self.x.uncover_others(r, &mut self.chooser);
+                self.x.uncommit(r, &mut self.chooser);
+            },
+            FinalState::AfterMultiColumnChoice { col_node }
+            | FinalState::AfterRemoveMultiRow { col_node } => {
+                self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+            },
+            FinalState::AfterPushMultiRow => {
+                let col_node = self.x.col(self.o[k]);
+                self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+            },
+            FinalState::ResumeMulti => {
+                let r = self.o[k];
+                let col_node = self.x.col(r);
+                self.x.uncover_others(r, &mut self.chooser);
+                self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+            },
+            FinalState::ResumeMultiSkip => {
+                let col_node = self.o[k];
+                self.x.reactivate(col_node, &mut self.chooser);
+                self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+            },
+        }
+    }
+
+    // Abandons the search and goes back to its start.
+    fn reset(&mut self) {
+        self.unwind();
+        self.nodes_since_solution = 0;
+        self.stack.push(FinalState::Start);
+        self.apply_assumptions().expect("assumptions were checked");
+        self.pin_subtree();
+    }
+
+    // Abandons the search and starts again from the top with the rows
+    // in a new random order.
+    fn restart(&mut self) -> SolverStep {
+        self.unwind();
+        if let Some(rng) = self.rng.as_mut() {
+            self.x.shuffle_columns(rng);
+        }
+        self.sort_by_cost();
+        self.reset();
+        SolverStep::Restart
+    }
+
+    // Searches from the start for every solution costing more than
+    // `above` and at most `limit`, as well as within any cost bound.
+    // Also returns the least cost of any solution that went over the
+    // limit but not the cost bound, if any might have.
+    pub(crate) fn solutions_in_cost_band(
+        &mut self, above: Option<u64>, limit: u64,
+    ) -> (Vec<(ExactCover, u64)>, Option<u64>) {
+        let old_limit = self.cost_limit;
+        self.cost_limit = Some(old_limit.map_or(limit, |l| l.min(limit)));
+        self.least_pruned_cost = None;
+        self.reset();
+        let mut band = vec![];
+        while let Some(solution) = self.next_solution() {
+            let cost = self.solution_cost(&solution);
+            if above.is_none_or(|above| cost > above) {
+                band.push((solution, cost));
+            }
+        }
+        self.cost_limit = old_limit;
+        let least_pruned = self.least_pruned_cost
+            .filter(|&c| old_limit.is_none_or(|l| c <= l));
+        (band, least_pruned)
+    }
+
+    /// Confines the search to the subtree at the end of the given path,
+    /// going back to its start. Only solutions containing every row
+    /// along the path are found from then on, including after the search
+    /// restarts or is reset. The paths from [`frontier`](Self::frontier)
+    /// name disjoint subtrees between them containing every solution, so
+    /// solvers restricted to each of them in turn, possibly on different
+    /// machines, find every solution exactly once. Pass the root path to
+    /// lift the restriction.
+    ///
+    /// If the path isn't valid for the problem, returns an error and
+    /// leaves the search unrestricted at its start.
+    pub fn restrict_to(&mut self, path: &SubtreePath) -> Result<(), SubtreeError> {
+        self.subtree = SubtreePath::root();
+        self.reset();
+        if path.depth() > 0 && self.x.has_multiplicities() {
+            return Err(SubtreeError::Multiplicities);
+        }
+        for (depth, &(col, row)) in path.choices().iter().enumerate() {
+            let col_node = col + 1;
+            let mut c = self.x.right(HEAD);
+            while c != HEAD && c != col_node {
+                c = self.x.right(c);
+            }
+            if c == HEAD {
+                self.reset();
+                return Err(SubtreeError::UnavailableColumn { depth, col_idx: col });
+            }
+            let mut r = self.x.down(c);
+            while r != c && self.x.row_label(r) != row {
+                r = self.x.down(r);
+            }
+            if r == c {
+                self.reset();
+                return Err(SubtreeError::UnavailableRow { depth, row_idx: row });
+            }
+            self.pin_row(r);
+        }
+        self.subtree = path.clone();
+        Ok(())
+    }
+
+    /// The path to the subtree the search is confined to, which is the
+    /// root path unless the solver has been restricted with
+    /// [`restrict_to`](Self::restrict_to).
+    pub fn subtree(&self) -> &SubtreePath {
+        &self.subtree
+    }
+
+    /// The paths to every node `depth` levels below the root of the
+    /// solver's subtree, in search order, together with the paths to any
+    /// solutions above that depth. Their subtrees are disjoint and
+    /// between them hold every solution in the solver's subtree. Costs
+    /// are ignored. The search is reset to its start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is nonzero and the problem has multiplicities.
+    pub fn frontier(&mut self, depth: usize) -> Vec<SubtreePath> {
+        assert!(depth == 0 || !self.x.has_multiplicities(),
+            "subtrees don't support multiplicities");
+        self.reset();
+        let mut paths = vec![];
+        let mut path = self.subtree.clone();
+        self.collect_frontier(depth, &mut path, &mut paths);
+        paths
+    }
+
+    fn collect_frontier(
+        &mut self,
+        depth: usize,
+        path: &mut SubtreePath,
+        paths: &mut Vec<SubtreePath>,
+    ) {
+        if depth == 0 || self.x.right(HEAD) == HEAD {
+            paths.push(path.clone());
+            return;
+        }
+        let (col_node, _) = self.x.choose(&mut self.chooser);
+        self.x.cover(col_node, &mut self.chooser);
+        let mut r = self.x.down(col_node);
+        while r != col_node {
+            self.x.cover_others(r, &mut self.chooser);
+            path.push(col_node-1, self.x.row_label(r));
+            self.collect_frontier(depth - 1, path, paths);
+            path.pop();
+            self.x.uncover_others(r, &mut self.chooser);
+            r = self.x.down(r);
+        }
+        self.x.uncover(col_node, &mut self.chooser);
+    }
+
+    // Adds the row containing node r to the solution at the start of the
+    // search, exactly as if the search had pushed it itself, and pins it
+    // there.
+    fn pin_row(&mut self, r: usize) {
+        self.stack.pop();
+        let k = self.stack.len();
+        self.x.cover(self.x.col(r), &mut self.chooser);
+        self.x.cover_others(r, &mut self.chooser);
+        self.o[k] = r;
+        self.cost += self.row_cost(r);
+        self.stack.push(FinalState::Resume);
+        self.stack.push(FinalState::Start);
+        self.floor = k + 1;
+    }
+
+    // Adds the optional row starting at node r to the solution at the
+    // start of the search, like `pin_row`. The row must be hidden, so that
+    // the search can't add it again.
+    fn pin_optional_row(&mut self, r: usize) {
+        let i = self.topology.optional_rows.binary_search(&r)
+            .expect("rows without primary columns are optional");
+        self.stack.pop();
+        let k = self.stack.len();
+        self.x.commit(r, &mut self.chooser);
+        self.x.cover_others(r, &mut self.chooser);
+        self.o[k] = r;
+        self.cost += self.row_cost(r);
+        self.stack.push(FinalState::ResumeOptional { i });
+        self.stack.push(FinalState::Start);
+        self.floor = k + 1;
+    }
+
+    // Hides the forbidden rows, and the forced rows without a 1 in a
+    // primary column, in that order.
+    fn hide_assumed_rows(&mut self) {
+        for row in self.assumed_hidden_rows() {
+            self.x.hide_row(self.topology.row_nodes[row], &mut self.chooser);
+        }
+    }
+
+    // Undoes `hide_assumed_rows`.
+    fn unhide_assumed_rows(&mut self) {
+        for row in self.assumed_hidden_rows().into_iter().rev() {
+            self.x.unhide_row(self.topology.row_nodes[row], &mut self.chooser);
+        }
+    }
+
+    fn assumed_hidden_rows(&self) -> Vec<usize> {
+        let optional = self.assumptions.forced.iter()
+            .filter(|&&row| self.x.primary_node(self.topology.row_nodes[row]).is_none());
+        self.assumptions.forbidden.iter().chain(optional).copied().collect()
+    }
+
+    // Hides the assumed rows and pins the forced ones in order, returning
+    // an error at the first that clashes with those before it.
+    fn apply_assumptions(&mut self) -> Result<(), AssumptionError> {
+        if self.assumptions.is_empty() {
+            return Ok(());
+        }
+        self.hide_assumed_rows();
+        for i in 0..self.assumptions.forced.len() {
+            let row_idx = self.assumptions.forced[i];
+            let first = self.topology.row_nodes[row_idx];
+            if self.x.row_conflicts(first) {
+                return Err(AssumptionError::Contradiction { row_idx });
+            }
+            match self.x.primary_node(first) {
+                Some(r) => self.pin_row(r),
+                None => self.pin_optional_row(first),
+            }
+        }
+        Ok(())
+    }
+
+    /// Goes back to the start of the search with the given rows forced
+    /// into every solution and the given rows forbidden from all of them,
+    /// in place of any earlier assumptions. This reuses the solver's
+    /// links, so is much cheaper than building a new solver for each set
+    /// of assumptions. Forced rows are reported as part of every solution
+    /// and partial solution. The assumptions last until the next call,
+    /// including through restarts and resets. Any restriction to a
+    /// subtree is lifted.
+    ///
+    /// If the problem has symmetries, every one of them must map the
+    /// forced rows to forced rows and the forbidden rows to forbidden
+    /// rows, so that the canonical solution of each class is still found.
+    ///
+    /// If the assumptions are invalid, contradict each other or aren't
+    /// kept by the symmetries, returns an error and leaves the search at
+    /// its start without any.
+    pub fn reset_with_assumptions(
+        &mut self, forced: &[usize], forbidden: &[usize],
+    ) -> Result<(), AssumptionError> {
+        self.subtree = SubtreePath::root();
+        self.unwind();
+        self.assumptions = Assumptions::default();
+        self.nodes_since_solution = 0;
+        self.stack.push(FinalState::Start);
+        let result = Assumptions::new(forced, forbidden,
+            &self.topology.row_nodes, self.x.has_multiplicities(),
+            &self.topology.symmetries)
+            .and_then(|assumptions| {
+                self.assumptions = assumptions;
+                self.apply_assumptions()
+            });
+        if result.is_err() {
+            self.unwind();
+            self.assumptions = Assumptions::default();
+            self.stack.push(FinalState::Start);
+        }
+        result
+    }
+
+    /// The rows forced into every solution, in the order they were given
+    /// to [`reset_with_assumptions`](Self::reset_with_assumptions).
+    pub fn forced_rows(&self) -> &[usize] {
+        &self.assumptions.forced
+    }
+
+    /// The rows forbidden from every solution, in the order they were
+    /// given to [`reset_with_assumptions`](Self::reset_with_assumptions).
+    pub fn forbidden_rows(&self) -> &[usize] {
+        &self.assumptions.forbidden
+    }
+
+    // Pins the rows along the path to the solver's subtree, which is
+    // known to be valid.
+    fn pin_subtree(&mut self) {
+        for i in 0..self.subtree.depth() {
+            let (col, row) = self.subtree.choices()[i];
+            let col_node = col + 1;
+            let mut r = self.x.down(col_node);
+            while self.x.row_label(r) != row {
+                r = self.x.down(r);
+            }
+            self.pin_row(r);
+        }
+    }
+
+    /// Returns a handle through which another thread can watch the
+    /// progress of the search and cancel it. Every handle on a solver
+    /// shares the same state.
+    pub fn handle(&mut self) -> SolverHandle {
+        let progress = self.progress.0
+            .get_or_insert_with(|| Arc::new(Progress::default()));
+        SolverHandle { progress: progress.clone() }
+    }
+
+    /// Whether the search has been cancelled through one of the solver's
+    /// handles, in which case it takes no more steps.
+    pub fn is_cancelled(&self) -> bool {
+        self.progress.0.as_ref().is_some_and(|p| p.is_cancelled())
+    }
+
+    /// Sets whether the solver records each step it takes, so that
+    /// [`prev_step`](Self::prev_step) can undo it. This is off by
+    /// default, as the record grows with every step. Turning it off
+    /// forgets the steps recorded so far, as does anything that takes the
+    /// search back to its start, including a restart.
+    pub fn set_record_steps(&mut self, record: bool) {
+        self.history = record.then(Vec::new);
+    }
+
+    /// Undoes the last step taken since steps started being recorded with
+    /// [`set_record_steps`](Self::set_record_steps), returning it. The
+    /// solver is left exactly as it was before the step, including its
+    /// [`current_partial_solution`](Self::current_partial_solution), so
+    /// stepping forward again takes the same step, except that randomised
+    /// solvers may break ties between columns differently. Statistics and
+    /// progress reported to handles are not undone. Returns `None` if no
+    /// steps have been recorded.
+    pub fn prev_step(&mut self) -> Option<SolverStep> {
+        loop {
+            let (step, record) = self.history.as_mut()?.pop()?;
+            while self.stack.len() > record.kept {
+                let st = self.stack.pop().expect("stack is above the kept levels");
+                self.leave_state(self.stack.len(), st);
+            }
+            for &(tag, value) in &record.states {
+                let st = self.replay_state(self.stack.len(), tag, value)
+                    .expect("recorded states can be redone");
+                self.stack.push(st);
+            }
+            self.cost = record.cost;
+            self.nodes_since_solution = record.nodes_since_solution;
+            self.least_pruned_cost = record.least_pruned_cost;
+            self.empty_subset = record.empty_subset;
+            if step.is_some() {
+                return step;
+            }
+        }
+    }
+
+    // The state of the solver before a step, with the top two states of
+    // the stack. A step only pops the state it starts from and, when that
+    // was a level's last state, the one below it, passing over at most
+    // one solution that isn't canonical in between, so it never changes
+    // the states under those.
+    fn step_record(&self) -> StepRecord {
+        let kept = self.stack.len().saturating_sub(2);
+        StepRecord {
+            kept,
+            states: self.stack[kept..].iter()
+                .enumerate()
+                .map(|(i, st)| self.encode_state(kept + i, st))
+                .collect(),
+            cost: self.cost,
+            nodes_since_solution: self.nodes_since_solution,
+            least_pruned_cost: self.least_pruned_cost,
+            empty_subset: self.empty_subset.clone(),
+        }
+    }
+
+    // Records a step taken from the state in `before`, keeping only the
+    // states it changed. Running out pops states without a step, so is
+    // recorded too, unless there is nothing before it to undo. Restarts
+    // can't be undone, and have already cleared the history.
+    fn record_step(&mut self, mut before: StepRecord, step: &Option<SolverStep>) {
+        if *step == Some(SolverStep::Restart) {
+            return;
+        }
+        debug_assert!(self.stack.len() >= before.kept, "a step popped a state it wasn't expected to");
+        let kept = before.states.iter()
+            .zip(self.stack.iter().enumerate().skip(before.kept))
+            .take_while(|&(&old, (k, st))| old == self.encode_state(k, st))
+            .count();
+        let unchanged = kept == before.states.len() && before.kept + kept == self.stack.len();
+        before.states.drain(..kept);
+        before.kept += kept;
+        if let Some(history) = self.history.as_mut()
+            && (step.is_some() || !(unchanged || history.is_empty()))
+        {
+            history.push((step.clone(), before));
+        }
+    }
+
     /// Return the next solver step if there are any remaining to take.
+    /// If the problem has symmetries, solutions that aren't the least of
+    /// their class are passed over without a step, after being found and
+    /// compared against every element of the symmetry group, so the
+    /// search takes as long as without the symmetries. Returns `None` once
+    /// the search has been cancelled through one of the solver's
+    /// [handles](Self::handle).
     pub fn next_step(&mut self) -> Option<SolverStep> {
-        while let Some(st) = self.stack.pop() {
+        if self.is_cancelled() {
+            return None;
+        }
+        let before = self.history.is_some().then(|| self.step_record());
+        let mut step = self.advance();
+        while let Some(SolverStep::ReportSolution(s)) = &step
+            && !symmetry::is_canonical(&self.topology.symmetries, &s.0)
+        {
+            step = self.advance();
+        }
+        if let Some(before) = before {
+            self.record_step(before, &step);
+        }
+        if self.progress.0.is_some() {
+            self.report_progress(&step);
+        }
+        #[cfg(feature = "stats")]
+        self.record_stats(&step);
+        step
+    }
+
+    /// The statistics on the search done by the solver since it was
+    /// created or its statistics were last reset. Only available with
+    /// the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> SolverStats {
+        SolverStats {
+            mems: self.x.mems() - self.stats.mems,
+            ..self.stats.clone()
+        }
+    }
+
+    /// Clears the solver's statistics. Only available with the `stats`
+    /// feature.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = SolverStats { mems: self.x.mems(), ..SolverStats::default() };
+    }
+
+    // Adds a step to the statistics. The root of the search is counted
+    // when the search starts from it instead.
+    #[cfg(feature = "stats")]
+    fn record_stats(&mut self, step: &Option<SolverStep>) {
+        match step {
+            Some(SolverStep::SelectColumn { size, .. }) => {
+                self.stats.add_branching(*size);
+            },
+            // The new row's state is on top of the stack.
+            Some(SolverStep::PushRow(_) | SolverStep::AdvanceRow(..)) => {
+                self.stats.add_node(self.stack.len());
+            },
+            Some(SolverStep::ReportSolution(_)) => {
+                let depth = match self.stack.last() {
+                    Some(FinalState::NextEmptyRowSubset { .. }) => self.stack.len() - 1,
+                    _ => self.stack.len(),
+                };
+                self.stats.add_solution(depth);
+            },
+            _ => (),
+        }
+    }
+
+    // Passes on the progress made by a step to the solver's handles.
+    fn report_progress(&self, step: &Option<SolverStep>) {
+        let Some(progress) = &self.progress.0 else { return };
+        let solution = matches!(step, Some(SolverStep::ReportSolution(_)));
+        progress.add_step(solution, self.stack.len());
+        if step.is_none() {
+            progress.set_fraction(1.0);
+        } else if progress.steps() % PROGRESS_INTERVAL == 0 {
+            progress.set_fraction(self.fraction_explored());
+        }
+    }
+
+    // Estimates the fraction of the search tree explored so far, taking
+    // every branch at a level to be as big as every other. The rows of a
+    // covered column stay linked, so the position of each level's row
+    // among its siblings can be read off the column.
+    fn fraction_explored(&self) -> f64 {
+        let mut fraction = 0.0;
+        let mut weight = 1.0;
+        for (k, st) in self.stack.iter().enumerate().skip(self.floor) {
+            let (index, size) = match st {
+                FinalState::AfterAddOrReplaceRow { .. }
+                | FinalState::Resume => {
+                    let r = self.o[k];
+                    self.position_in_column(self.x.col(r), self.x.col(r), r)
+                },
+                FinalState::AfterPushMultiRow | FinalState::ResumeMulti => {
+                    let r = self.o[k];
+                    self.position_in_column(self.x.col(r), self.ft[k], r)
+                },
+                // Skipping the column is the last branch.
+                FinalState::ResumeMultiSkip => {
+                    let col_node = self.o[k];
+                    let (size, _) = self.position_in_column(
+                        col_node, self.ft[k], col_node);
+                    (size, size + 1)
+                },
+                FinalState::AfterRemoveRow { .. }
+                | FinalState::AfterRemoveMultiRow { .. } => {
+                    fraction += weight;
+                    break;
+                },
+                _ => break,
+            };
+            // A level waiting to move on from its row has explored the
+            // whole branch below it.
+            let finished = k + 1 == self.stack.len() && matches!(st,
+                FinalState::Resume | FinalState::ResumeMulti
+                | FinalState::ResumeMultiSkip);
+            fraction += weight * (index + finished as usize) as f64
+                / size as f64;
+            weight /= size as f64;
+        }
+        fraction
+    }
+
+    // The number of rows of a column after `first` up to `r`, and the
+    // number of rows of the column from `first` onwards.
+    fn position_in_column(
+        &self, col_node: usize, first: usize, r: usize,
+    ) -> (usize, usize) {
+        let (mut index, mut size) = (0, 0);
+        let mut x = match first == col_node {
+            true => self.x.down(col_node),
+            false => first,
+        };
+        while x != col_node {
+            if x == r {
+                index = size;
+            }
+            size += 1;
+            x = self.x.down(x);
+        }
+        (index, size.max(1))
+    }
+
+    // Takes the next step of the search.
+    fn advance(&mut self) -> Option<SolverStep> {
+        while self.stack.len() > self.floor {
+            let Some(st) = self.stack.pop() else { break };
             let k = self.stack.len();
             match st {
                 FinalState::Start => {
-                    if self.x[HEAD].right == HEAD {
-                        let solution = ExactCover(self.o.iter()
-                            .take(k)
-                            .map(|&r| self.x[r].row_label)
-                            .collect::<Vec<_>>());
-                        return Some(
-                            SolverStep::ReportSolution(solution)
+                    let out_of_budget = self.rng.is_some() && self.restart_after
+                        .is_some_and(|n| self.nodes_since_solution >= n);
+                    if out_of_budget {
+                        self.stack.push(FinalState::Start);
+                        return Some(self.restart());
+                    }
+                    #[cfg(feature = "stats")]
+                    if k == self.floor {
+                        self.stats.add_node(k);
+                    }
+                    if self.exceeds_limit() {
+                        continue;
+                    }
+
+                    if self.x.right(HEAD) == HEAD {
+                        if self.topology.optional_rows.is_empty() {
+                            return Some(self.report_solution(k));
+                        } else {
+                            self.stack.push(FinalState::Optional { i: 0 });
+                        }
+                    } else if self.x.has_multiplicities() {
+                        let (col_node, size) = self.x
+                            .choose(&mut self.chooser);
+                        let slack = self.x.slack(col_node);
+                        self.ft[k] = self.x
+                            .begin_branch(col_node, &mut self.chooser);
+                        self.stack.push(
+                            FinalState::AfterMultiColumnChoice { col_node }
                         );
+
+                        return Some(SolverStep::SelectColumn {
+                            col: col_node-1, size, slack,
+                        });
                     } else {
-                        let (col_node, size) = self
-                            .least_col_with_least_ones();
+                        let (col_node, size) = self.x
+                            .choose(&mut self.chooser);
                         self.stack.push(
                             FinalState::AfterColumnChoice { col_node }
                         );
-                        self.cover(col_node);
+                        self.x.cover(col_node, &mut self.chooser);
 
                         return Some(SolverStep::SelectColumn {
-                            col: col_node-1, size
+                            col: col_node-1, size, slack: 0,
                         });
                     }
                 },
                 FinalState::AfterColumnChoice { col_node } => {
-                    let r = self.x[col_node].down;
-                    if r != col_node {
+                    // Rows are sorted by cost, so once one is too
+                    // expensive so are the rest.
+                    let r = self.x.down(col_node);
+                    if r != col_node && self.within_limit(r) {
                         // TODO: factor out duplication of first
                         // half of the loop.
-                        let newrow = self.x[r].row_label;
+                        let newrow = self.x.row_label(r);
                         self.o[k] = r;
+                        self.cost += self.row_cost(r);
 
                         self.stack.push(
                             FinalState::AfterAddOrReplaceRow { r }
                         );
+                        self.visit_node();
                         return Some(SolverStep::PushRow(newrow));
                     } else {
-                        self.uncover(col_node);
+                        self.x.uncover(col_node, &mut self.chooser);
                         return Some(
                             SolverStep::DeselectColumn(col_node-1)
                         );
                     }
                 },
                 FinalState::AfterAddOrReplaceRow { r } => {
-                    let mut j = self.x[r].right;
-                    while j != r {
-                        self.cover(self.x[j].col);
-                        j = self.x[j].right;
-                    }
+                    self.x.cover_others(r, &mut self.chooser);
 
                     self.stack.push(FinalState::Resume);
                     self.stack.push(FinalState::Start);
@@ -296,30 +1665,28 @@ impl ExactCoverSolver {
                 FinalState::Resume => {
                     // Second half of the loop
                     let mut r = self.o[k];
-                    let col_node = self.x[r].col;
-
-                    let mut j = self.x[r].left;
-                    while j != r {
-                        self.uncover(self.x[j].col);
+                    let col_node = self.x.col(r);
 
-                        j = self.x[j].left;
-                    }
+                    self.x.uncover_others(r, &mut self.chooser);
+                    self.cost -= self.row_cost(r);
 
-                    let previous_row = self.x[r].row_label;
+                    let previous_row = self.x.row_label(r);
 
-                    r = self.x[r].down;
+                    r = self.x.down(r);
                     // First half of the loop again. TODO factor out
                     // though now it's a resumption, so we know to
                     // REPLACE and REMOVE
-                    if r != col_node {
+                    if r != col_node && self.within_limit(r) {
                         // TODO: factor out duplication of first half
                         // of the loop.
-                        let newrow = self.x[r].row_label;
+                        let newrow = self.x.row_label(r);
                         self.o[k] = r;
+                        self.cost += self.row_cost(r);
 
                         self.stack.push(
                             FinalState::AfterAddOrReplaceRow { r }
                         );
+                        self.visit_node();
 
                         return Some(SolverStep::AdvanceRow(
                             previous_row, newrow
@@ -333,9 +1700,140 @@ impl ExactCoverSolver {
                     }
                 },
                 FinalState::AfterRemoveRow { col_node } => {
-                    self.uncover(col_node);
+                    self.x.uncover(col_node, &mut self.chooser);
                     return Some(SolverStep::DeselectColumn(col_node-1));
-                }
+                },
+                FinalState::Optional { i } => {
+                    let mut next = None;
+                    for i in i..self.topology.optional_rows.len() {
+                        let r = self.topology.optional_rows[i];
+                        if self.is_compatible(r) && self.within_limit(r) {
+                            next = Some(i);
+                            break;
+                        }
+                    }
+                    match next {
+                        Some(i) => {
+                            let r = self.topology.optional_rows[i];
+                            self.o[k] = r;
+                            self.cost += self.row_cost(r);
+                            self.stack.push(
+                                FinalState::AfterPushOptionalRow { i }
+                            );
+                            self.visit_node();
+                            return Some(
+                                SolverStep::PushRow(self.x.row_label(r))
+                            );
+                        },
+                        None => return Some(self.report_solution(k)),
+                    }
+                },
+                FinalState::AfterPushOptionalRow { i } => {
+                    let r = self.topology.optional_rows[i];
+                    self.x.commit(r, &mut self.chooser);
+                    self.x.cover_others(r, &mut self.chooser);
+
+                    self.stack.push(FinalState::ResumeOptional { i });
+                    self.stack.push(FinalState::Optional { i: i+1 });
+                },
+                FinalState::ResumeOptional { i } => {
+                    let r = self.topology.optional_rows[i];
+                    self.x.uncover_others(r, &mut self.chooser);
+                    self.x.uncommit(r, &mut self.chooser);
+                    self.cost -= self.row_cost(r);
+
+                    // Now try the subsets without this row.
+                    self.stack.push(FinalState::Optional { i: i+1 });
+                    return Some(SolverStep::PopRow(self.x.row_label(r)));
+                },
+                FinalState::AfterMultiColumnChoice { col_node } => {
+                    let x = self.ft[k];
+                    match self.x.next_branch(col_node, x, &mut self.chooser) {
+                        Branch::Row(r) => {
+                            self.o[k] = r;
+                            self.cost += self.row_cost(r);
+                            self.stack.push(FinalState::AfterPushMultiRow);
+                            self.visit_node();
+                            return Some(
+                                SolverStep::PushRow(self.x.row_label(r))
+                            );
+                        },
+                        Branch::Skip => {
+                            self.o[k] = col_node;
+                            self.stack.push(FinalState::ResumeMultiSkip);
+                            self.stack.push(FinalState::Start);
+                        },
+                        Branch::Exhausted => {
+                            self.x.end_branch(
+                                col_node, self.ft[k], &mut self.chooser);
+                            return Some(
+                                SolverStep::DeselectColumn(col_node-1)
+                            );
+                        },
+                    }
+                },
+                FinalState::AfterPushMultiRow => {
+                    self.x.cover_others(self.o[k], &mut self.chooser);
+
+                    self.stack.push(FinalState::ResumeMulti);
+                    self.stack.push(FinalState::Start);
+                },
+                FinalState::ResumeMulti => {
+                    let r = self.o[k];
+                    let col_node = self.x.col(r);
+                    self.x.uncover_others(r, &mut self.chooser);
+                    self.cost -= self.row_cost(r);
+                    let previous_row = self.x.row_label(r);
+
+                    // A tweaked row still links down to the next one.
+                    let x = self.x.down(r);
+                    match self.x.next_branch(col_node, x, &mut self.chooser) {
+                        Branch::Row(r) => {
+                            self.o[k] = r;
+                            self.cost += self.row_cost(r);
+                            self.stack.push(FinalState::AfterPushMultiRow);
+                            self.visit_node();
+                            return Some(SolverStep::AdvanceRow(
+                                previous_row, self.x.row_label(r)
+                            ));
+                        },
+                        Branch::Skip => {
+                            self.o[k] = col_node;
+                            self.stack.push(FinalState::ResumeMultiSkip);
+                            self.stack.push(FinalState::Start);
+                        },
+                        Branch::Exhausted => {
+                            self.stack.push(
+                                FinalState::AfterRemoveMultiRow { col_node }
+                            );
+                        },
+                    }
+                    return Some(SolverStep::PopRow(previous_row));
+                },
+                FinalState::ResumeMultiSkip => {
+                    let col_node = self.o[k];
+                    self.x.reactivate(col_node, &mut self.chooser);
+                    self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+                    return Some(SolverStep::DeselectColumn(col_node-1));
+                },
+                FinalState::AfterRemoveMultiRow { col_node } => {
+                    self.x.end_branch(col_node, self.ft[k], &mut self.chooser);
+                    return Some(SolverStep::DeselectColumn(col_node-1));
+                },
+                FinalState::NextEmptyRowSubset { len } => {
+                    while self.advance_empty_subset() {
+                        let cost = self.cost
+                            .saturating_add(self.empty_subset_cost());
+                        if self.check_limit(cost) {
+                            self.stack.push(
+                                FinalState::NextEmptyRowSubset { len }
+                            );
+                            return Some(SolverStep::ReportSolution(
+                                self.solution_with_empty_subset(len)
+                            ));
+                        }
+                    }
+                },
             }
         }
 
@@ -343,87 +1841,286 @@ impl ExactCoverSolver {
     }
 
     /// Returns an iterator through remaining solutions.
-    pub fn iter_solutions(&mut self) -> Solutions {
+    pub fn iter_solutions(&mut self) -> Solutions<'_, C> {
         Solutions { solver: self }
     }
 
-    // /// Returns an iterator through remaining solver steps.
-    pub fn iter_steps(&mut self) -> SolverSteps {
-        SolverSteps { solver: self }
+    /// Builds a zero-suppressed decision diagram of every solution in
+    /// the solver's subtree, including their extensions by the empty
+    /// rows, from which they can be counted, sampled and enumerated.
+    /// Subproblems are memoised on the state of the columns they start
+    /// from, so this can be exponentially faster than iterating through
+    /// the solutions. Costs, cost bounds and symmetries are ignored. The
+    /// search is reset to the start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the problem has multiplicities, whose search state
+    /// can't be memoised on the columns alone.
+    pub fn solution_zdd(&mut self) -> SolutionZdd {
+        assert!(!self.x.has_multiplicities(),
+            "solution ZDDs don't support multiplicities");
+        self.reset();
+        let mut builder = ZddBuilder::new();
+        let mut root = self.zdd_search(&mut builder);
+        for &(_, row) in self.subtree.choices().iter().rev() {
+            root = builder.node(row, builder.bottom(), root);
+        }
+        for &row in self.assumptions.forced.iter().rev() {
+            root = builder.node(row, builder.bottom(), root);
+        }
+        for &row in self.topology.empty_rows.iter().rev() {
+            root = builder.node(row, root, root);
+        }
+        builder.finish(root)
     }
 
-    // Returns index of the col node NOT THE COLUMN.
-    // and the smallest size.
-    fn least_col_with_least_ones(&self) -> (usize, usize) {
-        // We know at this point that HEAD.right != HEAD.
-        // otherwise we exit early in search.
-        // so we don't have to worry about returning
-        // usize::MAX or a non-col here.
-        let mut s = usize::MAX;
-        let mut j = self.x[HEAD].right;
-        let mut min_col = j;
-        while j != HEAD {
-            let j_size = self.x[j].size;
-            if j_size < s {
-                min_col = j;
-                s = j_size;
-            }
+    // The diagram of the solutions of the current subproblem.
+    fn zdd_search(&mut self, builder: &mut ZddBuilder) -> usize {
+        if self.x.right(HEAD) == HEAD {
+            return self.zdd_optional(builder, 0);
+        }
+        let mut key = vec![];
+        self.x.state_key(&mut key);
+        if let Some(node) = builder.lookup(&key) {
+            return node;
+        }
 
-            j = self.x[j].right;
+        let (col_node, _) = self.x.choose(&mut self.chooser);
+        self.x.cover(col_node, &mut self.chooser);
+        let mut branches = vec![];
+        let mut r = self.x.down(col_node);
+        while r != col_node {
+            self.x.cover_others(r, &mut self.chooser);
+            let node = self.zdd_search(builder);
+            self.x.uncover_others(r, &mut self.chooser);
+            branches.push((self.x.row_label(r), node));
+            r = self.x.down(r);
         }
+        self.x.uncover(col_node, &mut self.chooser);
 
-        (min_col, s)
+        // Each row of the column in turn, or one of the rows after it.
+        let mut node = builder.bottom();
+        for (row, hi) in branches.into_iter().rev() {
+            node = builder.node(row, node, hi);
+        }
+        builder.memoise(key, node);
+        node
     }
 
-    // Covers a column node c. (This is the actual node, so will have
-    // index in [1, ... , num_cols].)
-    fn cover(&mut self, c: usize) {
-        let r = self.x[c].right;
-        self.x[r].left = self.x[c].left;
-        let l = self.x[c].left;
-        self.x[l].right = self.x[c].right;
+    // The diagram of the subsets of the optional rows from index i
+    // onwards compatible with the current solution.
+    fn zdd_optional(&mut self, builder: &mut ZddBuilder, i: usize) -> usize {
+        let Some(i) = (i..self.topology.optional_rows.len())
+            .find(|&i| self.is_compatible(self.topology.optional_rows[i]))
+        else {
+            return builder.top();
+        };
+        let mut key = vec![i as u64];
+        self.x.state_key(&mut key);
+        if let Some(node) = builder.lookup(&key) {
+            return node;
+        }
 
-        let mut i = self.x[c].down;
-        while i != c {
-            let mut j = self.x[i].right;
-            while j != i {
-                let d = self.x[j].down;
-                self.x[d].up = self.x[j].up;
-                let u = self.x[j].up;
-                self.x[u].down = self.x[j].down;
-                let c_j = self.x[j].col;
-                self.x[c_j].size -= 1;
+        let r = self.topology.optional_rows[i];
+        let lo = self.zdd_optional(builder, i+1);
+        self.x.commit(r, &mut self.chooser);
+        self.x.cover_others(r, &mut self.chooser);
+        let hi = self.zdd_optional(builder, i+1);
+        self.x.uncover_others(r, &mut self.chooser);
+        self.x.uncommit(r, &mut self.chooser);
 
-                j = self.x[j].right;
-            }
+        let node = builder.node(self.x.row_label(r), lo, hi);
+        builder.memoise(key, node);
+        node
+    }
 
-            i = self.x[i].down;
+    /// Estimates the size of the search of the solver's subtree without
+    /// carrying it out, by following `samples` random paths from its root
+    /// to a leaf (Knuth's estimator). At each level the path branches on
+    /// the column the search would choose, among as many rows as the
+    /// corresponding [`SolverStep::SelectColumn`] reports that are within
+    /// any cost bound. Each estimate is unbiased, but the variance can be
+    /// huge on lopsided trees, so it is reported too. The same seed
+    /// always gives the same estimates. The search is reset to the start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `samples` is zero or the problem has multiplicities.
+    pub fn estimate_tree_size(&mut self, samples: u64, seed: u64) -> TreeSizeEstimate {
+        assert!(samples > 0, "tree size estimates need at least one sample");
+        assert!(!self.x.has_multiplicities(),
+            "tree size estimates don't support multiplicities");
+        let mut rng = SplitMix64::new(seed);
+        let mut nodes = Samples::default();
+        let mut solutions = Samples::default();
+        #[cfg(feature = "stats")]
+        let mut mems = Samples::default();
+        self.reset();
+        for _ in 0..samples {
+            let (n, s, _m) = self.random_path(&mut rng);
+            nodes.add(n);
+            solutions.add(s);
+            #[cfg(feature = "stats")]
+            mems.add(_m);
+        }
+        self.reset();
+        TreeSizeEstimate {
+            nodes: nodes.estimate(),
+            solutions: solutions.estimate(),
+            #[cfg(feature = "stats")]
+            mems: mems.estimate(),
         }
     }
 
-    // Uncovers a column node c. (This is the actual node, so will have
-    // index in [1, ... , num_cols].)
-    fn uncover(&mut self, c: usize) {
-        let mut i = self.x[c].up;
-        while i != c {
-            let mut j = self.x[i].left;
-            while j != i {
-                let c_j = self.x[j].col;
-                self.x[c_j].size += 1;
-                let d = self.x[j].down;
-                self.x[d].up = j;
-                let u = self.x[j].up;
-                self.x[u].down = j;
+    // Follows a random path from the current node to a leaf, returning
+    // Knuth's estimates of the number of nodes, solutions and mems below
+    // it, and then undoes the path.
+    fn random_path(&mut self, rng: &mut SplitMix64) -> (f64, f64, f64) {
+        let start_cost = self.cost;
+        let over = |cost: u64, limit: Option<u64>| limit.is_some_and(|l| cost > l);
+        // The number of nodes on each level is estimated by `weight`.
+        let (mut weight, mut nodes, mut solutions, mut mems) = (1.0, 1.0, 0.0, 0.0);
+        // The column covered at each level, the row chosen if any, and
+        // the estimated numbers of nodes on the level and the next.
+        let mut path = vec![];
 
-                j = self.x[j].left;
+        // Down through the primary columns.
+        let mut dead_end = false;
+        while self.x.right(HEAD) != HEAD {
+            if !self.topology.row_costs.is_empty() {
+                let bound = self.x.cost_lower_bound(&self.topology.row_costs);
+                if over(self.cost.saturating_add(bound), self.cost_limit) {
+                    dead_end = true;
+                    break;
+                }
             }
+            let (col_node, _) = self.x.choose(&mut self.chooser);
+            let before = self.mems_spent();
+            self.x.cover(col_node, &mut self.chooser);
+            mems += weight * (self.mems_spent() - before) as f64;
 
-            i = self.x[i].up;
+            // Rows are sorted by cost, so those within the limit come
+            // first.
+            let mut rows = 0;
+            let mut r = self.x.down(col_node);
+            while r != col_node
+                && !over(self.cost.saturating_add(self.row_cost(r)), self.cost_limit) {
+                rows += 1;
+                r = self.x.down(r);
+            }
+            if rows == 0 {
+                path.push((col_node, None, weight, 0.0));
+                dead_end = true;
+                break;
+            }
+            let mut r = self.x.down(col_node);
+            for _ in 0..rng.below(rows) {
+                r = self.x.down(r);
+            }
+            let parent_weight = weight;
+            weight *= rows as f64;
+            nodes += weight;
+            let before = self.mems_spent();
+            self.x.cover_others(r, &mut self.chooser);
+            mems += weight * (self.mems_spent() - before) as f64;
+            self.cost += self.row_cost(r);
+            path.push((col_node, Some(r), parent_weight, weight));
         }
 
-        let r = self.x[c].right;
-        self.x[r].left = c;
-        let l = self.x[c].left;
-        self.x[l].right = c;
+        // Every node below the primary columns is a solution, extended
+        // by each compatible optional row in turn.
+        let mut optional = vec![];
+        if !dead_end {
+            let mut i = 0;
+            loop {
+                solutions += weight * self.random_empty_row_extensions(rng);
+                let fits = |s: &Self, r: usize| s.is_compatible(r)
+                    && !over(s.cost.saturating_add(s.row_cost(r)), s.cost_limit);
+                let candidates = (i..self.topology.optional_rows.len())
+                    .filter(|&j| fits(self, self.topology.optional_rows[j]))
+                    .collect::<Vec<_>>();
+                if candidates.is_empty() {
+                    break;
+                }
+                let j = candidates[rng.below(candidates.len())];
+                let r = self.topology.optional_rows[j];
+                weight *= candidates.len() as f64;
+                nodes += weight;
+                let before = self.mems_spent();
+                self.x.commit(r, &mut self.chooser);
+                self.x.cover_others(r, &mut self.chooser);
+                mems += weight * (self.mems_spent() - before) as f64;
+                self.cost += self.row_cost(r);
+                optional.push((r, weight));
+                i = j + 1;
+            }
+        }
+
+        // Undoing the path costs what the search would spend backtracking.
+        for (r, weight) in optional.into_iter().rev() {
+            let before = self.mems_spent();
+            self.x.uncover_others(r, &mut self.chooser);
+            self.x.uncommit(r, &mut self.chooser);
+            mems += weight * (self.mems_spent() - before) as f64;
+        }
+        for (col_node, r, parent_weight, weight) in path.into_iter().rev() {
+            if let Some(r) = r {
+                let before = self.mems_spent();
+                self.x.uncover_others(r, &mut self.chooser);
+                mems += weight * (self.mems_spent() - before) as f64;
+            }
+            let before = self.mems_spent();
+            self.x.uncover(col_node, &mut self.chooser);
+            mems += parent_weight * (self.mems_spent() - before) as f64;
+        }
+        self.cost = start_cost;
+        (nodes, solutions, mems)
+    }
+
+    // Estimates the number of solutions the current solution is reported
+    // as, one for each subset of the empty rows within the cost limit, by
+    // trying a random subset.
+    fn random_empty_row_extensions(&self, rng: &mut SplitMix64) -> f64 {
+        let empty_rows = &self.topology.empty_rows;
+        if !self.expand_empty_rows {
+            return 1.0;
+        }
+        let subsets = 2f64.powi(empty_rows.len() as i32);
+        let Some(limit) = self.cost_limit else { return subsets };
+        if self.topology.row_costs.is_empty() {
+            return subsets;
+        }
+        let cost = empty_rows.iter()
+            .filter(|_| rng.below(2) == 0)
+            .fold(self.cost, |c, &r| c.saturating_add(self.topology.row_costs[r]));
+        match cost <= limit {
+            true => subsets,
+            false => 0.0,
+        }
+    }
+
+    /// Returns an iterator through all solutions in order of increasing
+    /// cost, each with its cost, restarting the search from the start.
+    /// Solutions of equal cost come in the order the search finds them.
+    /// If a cost bound is set, only solutions within it are found.
+    ///
+    /// This works in rounds, each a search for the solutions in the
+    /// next band of costs. The bands are as narrow as the pruning allows,
+    /// so finding the few cheapest solutions never needs more memory
+    /// than those of equal cost, at the price of searching the cheap
+    /// part of the tree repeatedly.
+    pub fn iter_solutions_by_cost(&mut self) -> SolutionsByCost<'_, C> {
+        SolutionsByCost {
+            solver: self,
+            band: vec![],
+            next_limit: Some(0),
+            found_up_to: None,
+        }
+    }
+
+    /// Returns an iterator through remaining solver steps.
+    pub fn iter_steps(&mut self) -> SolverSteps<'_, C> {
+        SolverSteps { solver: self }
     }
 }
@@ -0,0 +1,61 @@
+//! Statistics on the shape of a search, with the `stats` feature.
+//!
+//! These follow the profiles Knuth gives for his DLX programs, so that
+//! runs can be compared with his published numbers. Without the feature
+//! none of this is compiled, and the search does no extra work.
+
+/// Statistics on the search done by a solver so far. This `struct` is
+/// created by the [`stats`](super::ExactCoverSolver::stats) method on
+/// [`ExactCoverSolver`](super::ExactCoverSolver).
+///
+/// Depths count the levels of the search tree, so the root is at depth
+/// 0 and a node at depth `d` has `d` choices above it. Every vector is
+/// exactly long enough to hold its last nonzero entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolverStats {
+    /// The mems spent covering and uncovering columns.
+    pub mems: u64,
+    /// The number of nodes visited at each depth, i.e. the number of
+    /// rows pushed or advanced to there, with the root counted once per
+    /// search from the top.
+    pub nodes_per_depth: Vec<u64>,
+    /// The number of columns branched on with each number of rows, as
+    /// reported by [`SolverStep::SelectColumn`](super::SolverStep).
+    pub branching: Vec<u64>,
+    /// The number of solutions found at each depth.
+    pub solutions_per_depth: Vec<u64>,
+    /// The deepest node visited.
+    pub max_depth: usize,
+}
+
+impl SolverStats {
+    /// The total number of nodes visited.
+    pub fn nodes(&self) -> u64 {
+        self.nodes_per_depth.iter().sum()
+    }
+
+    /// The total number of solutions found.
+    pub fn solutions(&self) -> u64 {
+        self.solutions_per_depth.iter().sum()
+    }
+
+    pub(crate) fn add_node(&mut self, depth: usize) {
+        bump(&mut self.nodes_per_depth, depth);
+        self.max_depth = self.max_depth.max(depth);
+    }
+
+    pub(crate) fn add_branching(&mut self, size: usize) {
+        bump(&mut self.branching, size);
+    }
+
+    pub(crate) fn add_solution(&mut self, depth: usize) {
+        bump(&mut self.solutions_per_depth, depth);
+    }
+}
+
+fn bump(counts: &mut Vec<u64>, i: usize) {
+    if counts.len() <= i {
+        counts.resize(i + 1, 0);
+    }
+    counts[i] += 1;
+}
@@ -0,0 +1,113 @@
+//! Names for subtrees of the search.
+//!
+//! A subtree is named by the path of choices leading to its root: at each
+//! level, the column branched on and the row chosen to cover it. Paths
+//! refer to columns and rows by their indices in the problem, so they
+//! make sense to any solver for the same problem, whatever its internal
+//! layout, and can be sent to other processes or machines as bytes.
+
+use super::checkpoint::{read_varint, write_varint};
+
+/// The path from the root of the search tree to the root of one of its
+/// subtrees, as a sequence of `(column, row)` choices.
+///
+/// Paths are produced by the [`frontier`](super::ExactCoverSolver::frontier)
+/// method on [`ExactCoverSolver`](super::ExactCoverSolver), and a solver
+/// can be confined to the subtree below one with its
+/// [`restrict_to`](super::ExactCoverSolver::restrict_to) method.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SubtreePath(Vec<(usize, usize)>);
+
+/// Something that can go wrong while restricting a solver to a subtree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtreeError {
+    /// The column chosen at `depth` was out of bounds, secondary, or
+    /// already covered by an earlier choice.
+    UnavailableColumn { depth: usize, col_idx: usize },
+    /// The row chosen at `depth` doesn't have a 1 in the chosen column,
+    /// or clashes with an earlier choice.
+    UnavailableRow { depth: usize, row_idx: usize },
+    /// The problem has multiplicities, whose search trees can't be
+    /// addressed this way.
+    Multiplicities,
+}
+
+impl SubtreePath {
+    /// The path made up of the given `(column, row)` choices.
+    pub fn new(choices: Vec<(usize, usize)>) -> Self {
+        Self(choices)
+    }
+
+    /// The empty path, naming the whole search tree.
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// The `(column, row)` choices along the path.
+    pub fn choices(&self) -> &[(usize, usize)] {
+        &self.0
+    }
+
+    /// The number of choices along the path.
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Encodes the path compactly as bytes: the depth followed by each
+    /// column and row, all as LEB128 variable-length integers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        write_varint(&mut bytes, self.0.len() as u64);
+        for &(col, row) in &self.0 {
+            write_varint(&mut bytes, col as u64);
+            write_varint(&mut bytes, row as u64);
+        }
+        bytes
+    }
+
+    /// Decodes a path encoded by [`to_bytes`](Self::to_bytes). Returns
+    /// `None` if the bytes aren't exactly one encoded path.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut bytes = bytes.iter().copied();
+        let mut read = || read_varint(&mut bytes)
+            .and_then(|n| usize::try_from(n).ok());
+        let depth = read()?;
+        let mut choices = vec![];
+        for _ in 0..depth {
+            let col = read()?;
+            let row = read()?;
+            choices.push((col, row));
+        }
+        match bytes.next() {
+            Some(_) => None,
+            None => Some(Self(choices)),
+        }
+    }
+
+    pub(crate) fn push(&mut self, col: usize, row: usize) {
+        self.0.push((col, row));
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.0.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        for choices in [vec![], vec![(0, 0)], vec![(3, 127), (128, 1 << 40)],
+            vec![(usize::MAX, 5)]] {
+            let path = SubtreePath::new(choices);
+            assert_eq!(SubtreePath::from_bytes(&path.to_bytes()), Some(path));
+        }
+        // Truncated, trailing and overlong encodings.
+        assert_eq!(SubtreePath::from_bytes(&[]), None);
+        assert_eq!(SubtreePath::from_bytes(&[1, 3]), None);
+        assert_eq!(SubtreePath::from_bytes(&[0, 0]), None);
+        assert_eq!(SubtreePath::from_bytes(&[0xff; 12]), None);
+    }
+}
@@ -0,0 +1,155 @@
+//! Symmetries of problems, and picking one solution from each class of
+//! symmetric solutions.
+//!
+//! A symmetry permutes the rows and columns of a problem so that it maps
+//! the problem to itself, and so maps solutions to solutions. Of the
+//! solutions that symmetries map to one another, the canonical one is
+//! the least when each is written as its sorted rows.
+//!
+//! The whole group generated by the given symmetries is kept as a list
+//! of row permutations, and the solver checks each solution it finds
+//! against every one of them, passing over those that aren't canonical.
+//! The search itself isn't pruned, so it visits every solution of every
+//! class, and each solution costs time proportional to the size of the
+//! group times the number of rows in it on top. The group is therefore
+//! limited to [`Symmetry::MAX_GROUP_SIZE`] elements.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use super::ExactCoverProblem;
+
+/// A symmetry of an exact cover problem: a permutation of its rows
+/// together with a permutation of its columns that maps the problem to
+/// itself. Symmetries are given to a problem with its
+/// [`set_symmetries`](ExactCoverProblem::set_symmetries) method.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symmetry {
+    rows: Vec<usize>,
+    columns: Vec<usize>,
+}
+
+impl Symmetry {
+    /// The most elements, counting the identity, that the group generated
+    /// by the symmetries of a problem may have. Each element takes a
+    /// `usize` per row of the problem.
+    pub const MAX_GROUP_SIZE: usize = 1 << 12;
+
+    /// The symmetry mapping each row `r` to `rows[r]` and each column `j`
+    /// to `columns[j]`.
+    pub fn new(rows: Vec<usize>, columns: Vec<usize>) -> Self {
+        Self { rows, columns }
+    }
+
+    /// The symmetry of a problem mapping each row `r` to `rows[r]`, with
+    /// the permutation of the columns worked out from where their rows
+    /// go. Returns `None` if the rows don't keep their costs or no
+    /// permutation of the columns fits.
+    pub fn from_rows(problem: &ExactCoverProblem, rows: Vec<usize>) -> Option<Self> {
+        if !is_permutation(&rows, problem.rows())
+            || (0..rows.len()).any(|r| problem.cost(r) != problem.cost(rows[r]))
+        {
+            return None;
+        }
+        // The `(row, colour)` entries in each column.
+        let mut col_entries = vec![vec![]; problem.columns()];
+        for (r, row) in problem.ordered_coloured_rows().enumerate() {
+            for (j, colour) in row {
+                col_entries[j].push((r, colour));
+            }
+        }
+        // Columns with the same entries are interchangeable, so long as
+        // they are of the same kind.
+        let primary = problem.primary_columns();
+        let kind = |j: usize| (j < primary, problem.multiplicity(j));
+        let mut by_entries = HashMap::<_, Vec<usize>>::new();
+        for (j, entries) in col_entries.iter().enumerate().rev() {
+            by_entries.entry((kind(j), entries.clone())).or_default().push(j);
+        }
+        let mut columns = Vec::with_capacity(problem.columns());
+        for (j, entries) in col_entries.iter().enumerate() {
+            let mut image = entries.iter()
+                .map(|&(r, colour)| (rows[r], colour))
+                .collect::<Vec<_>>();
+            image.sort_unstable();
+            columns.push(by_entries.get_mut(&(kind(j), image))?.pop()?);
+        }
+        Some(Self { rows, columns })
+    }
+
+    /// The row that row `r` is mapped to.
+    pub fn row(&self, r: usize) -> usize {
+        self.rows[r]
+    }
+
+    /// The column that column `j` is mapped to.
+    pub fn column(&self, j: usize) -> usize {
+        self.columns[j]
+    }
+
+    pub(crate) fn rows(&self) -> &[usize] {
+        &self.rows
+    }
+
+    pub(crate) fn columns(&self) -> &[usize] {
+        &self.columns
+    }
+}
+
+// Whether `perm` is a permutation of `0..n`.
+pub(crate) fn is_permutation(perm: &[usize], n: usize) -> bool {
+    let mut seen = vec![false; n];
+    perm.len() == n && perm.iter()
+        .all(|&i| i < n && !std::mem::replace(&mut seen[i], true))
+}
+
+// Every row permutation in the group generated by the given ones, other
+// than the identity, or `None` if the group has more than
+// `Symmetry::MAX_GROUP_SIZE` elements.
+pub(crate) fn close_group(generators: &[&[usize]], n: usize) -> Option<Vec<Vec<usize>>> {
+    let identity = (0..n).collect::<Vec<_>>();
+    let mut seen = HashSet::from([identity.clone()]);
+    let mut group = vec![identity];
+    let mut i = 0;
+    while i < group.len() {
+        for g in generators {
+            let product = group[i].iter().map(|&r| g[r]).collect::<Vec<_>>();
+            if seen.insert(product.clone()) {
+                if group.len() == Symmetry::MAX_GROUP_SIZE {
+                    return None;
+                }
+                group.push(product);
+            }
+        }
+        i += 1;
+    }
+    group.remove(0);
+    Some(group)
+}
+
+// The image of a set of rows under a row permutation, sorted.
+fn image(perm: &[usize], rows: &[usize]) -> Vec<usize> {
+    let mut image = rows.iter().map(|&r| perm[r]).collect::<Vec<_>>();
+    image.sort_unstable();
+    image
+}
+
+// Whether a solution is the least of its images under a group.
+pub(crate) fn is_canonical(group: &[Vec<usize>], rows: &[usize]) -> bool {
+    if group.is_empty() {
+        return true;
+    }
+    let mut sorted = rows.to_vec();
+    sorted.sort_unstable();
+    group.iter().all(|g| sorted <= image(g, &sorted))
+}
+
+// The number of distinct images of a solution under a group, counting
+// itself.
+pub(crate) fn orbit_size(group: &[Vec<usize>], rows: &[usize]) -> usize {
+    let mut sorted = rows.to_vec();
+    sorted.sort_unstable();
+    let mut images = group.iter().map(|g| image(g, &sorted)).collect::<HashSet<_>>();
+    images.insert(sorted);
+    images.len()
+}
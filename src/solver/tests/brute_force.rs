@@ -0,0 +1,835 @@
+//! Checks the solver against brute force on small random problems.
+
+use crate::solver::{
+    AssumptionError, CheckpointError, ExactCover, ExactCoverProblem, ExactCoverSolver, MrvBuckets,
+    ParallelSolver, SolverStep, SubtreePath, Symmetry, rng::SplitMix64,
+};
+use crate::solver::checkpoint::{tag, SavedState};
+use super::cases::{KnuthSimple, TestCase, sort_solutions};
+
+/// Every exact cover of the problem, found by trying every subset of
+/// its rows. Only usable for problems with a handful of rows.
+pub fn brute_force_solutions(problem: &ExactCoverProblem) -> Vec<ExactCover> {
+    let rows = problem.ordered_coloured_rows()
+        .map(|row| row.collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let primary = problem.primary_columns();
+    let mut solutions = vec![];
+    for subset in 0..1u64 << rows.len() {
+        let chosen = (0..rows.len())
+            .filter(|&i| subset >> i & 1 == 1)
+            .collect::<Vec<_>>();
+        // For each column, the number of times it is covered and the
+        // colours it is given.
+        let mut counts = vec![0; problem.columns()];
+        let mut colours = vec![vec![]; problem.columns()];
+        for &i in &chosen {
+            for &(j, colour) in &rows[i] {
+                counts[j] += 1;
+                colours[j].push(colour);
+            }
+        }
+        let valid = (0..problem.columns()).all(|j| if j < primary {
+            let (lo, hi) = problem.multiplicity(j);
+            lo <= counts[j] && counts[j] <= hi
+        } else {
+            counts[j] <= 1
+                || colours[j].iter().all(|&c| c != 0 && c == colours[j][0])
+        });
+        if valid {
+            solutions.push(ExactCover(chosen));
+        }
+    }
+    solutions
+}
+
+/// A random coloured problem with the given dimensions, where each
+/// entry is a 1 with probability `1/one_in` and each 1 in a secondary
+/// column is given one of `num_colours` colours or left uncoloured.
+pub fn random_coloured_problem(
+    rng: &mut SplitMix64,
+    num_rows: usize,
+    num_cols: usize,
+    num_secondary: usize,
+    num_colours: usize,
+    one_in: usize,
+) -> ExactCoverProblem {
+    let primary = num_cols - num_secondary;
+    let mut rows = vec![];
+    for _ in 0..num_rows {
+        let mut row = vec![];
+        for j in 0..num_cols {
+            if rng.below(one_in) == 0 {
+                let colour = if j < primary { 0 } else { rng.below(num_colours + 1) };
+                row.push((j, colour));
+            }
+        }
+        rows.push(row);
+    }
+    ExactCoverProblem::new_coloured(
+        rows.into_iter().map(|r| r.into_iter()), num_cols, num_secondary)
+        .unwrap()
+}
+
+/// A random problem of fewer than `max_rows` rows and `max_cols`
+/// columns, any number of them secondary and with up to two colours.
+/// With `with_multiplicities`, half the time its primary columns are
+/// given random multiplicities too.
+pub fn random_problem(
+    rng: &mut SplitMix64,
+    max_rows: usize,
+    max_cols: usize,
+    with_multiplicities: bool,
+) -> ExactCoverProblem {
+    random_sparse_problem(rng, max_rows, max_cols, 3, with_multiplicities)
+}
+
+/// As [`random_problem`], with each entry a 1 with probability
+/// `1/one_in`.
+pub fn random_sparse_problem(
+    rng: &mut SplitMix64,
+    max_rows: usize,
+    max_cols: usize,
+    one_in: usize,
+    with_multiplicities: bool,
+) -> ExactCoverProblem {
+    let num_rows = rng.below(max_rows);
+    let num_cols = rng.below(max_cols);
+    let num_secondary = rng.below(num_cols + 1);
+    let num_colours = rng.below(3);
+    let mut problem = random_coloured_problem(
+        rng, num_rows, num_cols, num_secondary, num_colours, one_in);
+    if with_multiplicities && rng.below(2) == 0 {
+        problem.set_multiplicities(random_multiplicities(rng, &problem)).unwrap();
+    }
+    problem
+}
+
+/// Random multiplicities for the primary columns of the problem, each
+/// a range of up to three counts that doesn't allow only 0.
+pub fn random_multiplicities(
+    rng: &mut SplitMix64,
+    problem: &ExactCoverProblem,
+) -> Vec<(usize, usize)> {
+    (0..problem.primary_columns())
+        .map(|_| {
+            let lo = rng.below(3);
+            (lo, lo + rng.below(3)).max((lo, 1))
+        })
+        .collect()
+}
+
+#[test]
+fn check_random_coloured_problems() {
+    let mut rng = SplitMix64::new(2024);
+    for _ in 0..300 {
+        let problem = random_problem(&mut rng, 11, 7, false);
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        let actual = ExactCoverSolver::new(&problem)
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let actual = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new())
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let actual = ExactCoverSolver::new_randomised(&problem, rng.next_u64())
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+    }
+}
+
+#[test]
+fn check_random_multiplicity_problems() {
+    let mut rng = SplitMix64::new(7);
+    for _ in 0..400 {
+        let mut problem = random_problem(&mut rng, 11, 6, false);
+        problem.set_multiplicities(random_multiplicities(&mut rng, &problem)).unwrap();
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        let actual = ExactCoverSolver::new(&problem)
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let actual = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new())
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let actual = ExactCoverSolver::new_randomised(&problem, rng.next_u64())
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        // Reducing keeps every row and the multiplicity of every column,
+        // unless every multiplicity happened to be (1, 1).
+        let (reduced, reduction) = problem.reduce();
+        if problem.has_multiplicities() {
+            assert_eq!(reduced.rows(), problem.rows());
+        }
+        for c in 0..reduced.primary_columns() {
+            assert_eq!(reduced.multiplicity(c), problem.multiplicity(reduction.original_column(c)));
+        }
+        let lifted = ExactCoverSolver::new(&reduced)
+            .iter_solutions()
+            .map(|solution| reduction.lift(&solution))
+            .collect::<Vec<_>>();
+        assert_eq!(sort_solutions(lifted), expected);
+    }
+}
+
+#[test]
+fn check_multiplicity_steps() {
+    // Column 0 must be covered 1 or 2 times by rows 0, 1 and 2.
+    let mut problem = ExactCoverProblem::new(
+        [vec![0], vec![0], vec![0]].into_iter().map(|r| r.into_iter()), 1, 0)
+        .unwrap();
+    problem.set_multiplicities(vec![(1, 2)]).unwrap();
+    let mut solver = ExactCoverSolver::new(&problem);
+    let steps = solver.iter_steps().collect::<Vec<_>>();
+    let selections = steps.iter()
+        .filter_map(|s| match *s {
+            SolverStep::SelectColumn { col, size, slack } => Some((col, size, slack)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // At the top, any of the three rows may be first. Below that, either
+    // of the remaining two may be added, or neither.
+    assert_eq!(selections[0], (0, 3, 1));
+    assert_eq!(selections[1], (0, 3, 1));
+    let solutions = steps.into_iter()
+        .filter_map(|s| match s {
+            SolverStep::ReportSolution(s) => Some(s),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(sort_solutions(solutions), sort_solutions(brute_force_solutions(&problem)));
+}
+
+#[test]
+fn check_random_cost_problems() {
+    let mut rng = SplitMix64::new(99);
+    for _ in 0..300 {
+        let mut problem = random_problem(&mut rng, 11, 6, true);
+        let costs = (0..problem.rows()).map(|_| rng.below(10) as u64).collect();
+        problem.set_costs(costs).unwrap();
+
+        let all = sort_solutions(brute_force_solutions(&problem));
+        let min_cost = all.iter().map(|s| problem.solution_cost(s)).min();
+        let bound = rng.below(25) as u64;
+        let expected = all.iter()
+            .filter(|s| problem.solution_cost(s) <= bound)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut solver = ExactCoverSolver::new(&problem);
+        solver.set_cost_bound(Some(bound));
+        let actual = solver.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let mut solver = ExactCoverSolver::new_randomised(&problem, rng.next_u64());
+        solver.set_cost_bound(Some(bound));
+        let actual = solver.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+
+        let best = ExactCoverSolver::new(&problem).min_cost_solution();
+        assert_eq!(best.as_ref().map(|(_, cost)| *cost), min_cost);
+        if let Some((solution, cost)) = best {
+            assert_eq!(problem.solution_cost(&solution), cost);
+            assert!(all.contains(&sort_solutions(vec![solution]).remove(0)));
+        }
+    }
+}
+
+#[test]
+fn check_random_solutions_by_cost() {
+    let mut rng = SplitMix64::new(5);
+    for _ in 0..200 {
+        let mut problem = random_problem(&mut rng, 10, 6, false);
+        let costs = (0..problem.rows()).map(|_| rng.below(6) as u64).collect();
+        problem.set_costs(costs).unwrap();
+
+        let mut expected = brute_force_solutions(&problem).into_iter()
+            .map(|s| problem.solution_cost(&s))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+
+        let mut solver = ExactCoverSolver::new(&problem);
+        let by_cost = solver.iter_solutions_by_cost().collect::<Vec<_>>();
+        for (solution, cost) in &by_cost {
+            assert_eq!(problem.solution_cost(solution), *cost);
+        }
+        assert_eq!(by_cost.iter().map(|(_, c)| *c).collect::<Vec<_>>(), expected);
+        assert_eq!(
+            sort_solutions(by_cost.into_iter().map(|(s, _)| s).collect()),
+            sort_solutions(brute_force_solutions(&problem)),
+        );
+
+        // Taking the first few, and with a cost bound.
+        let cheapest = solver.iter_solutions_by_cost()
+            .take(3)
+            .map(|(_, c)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(cheapest, expected.iter().copied().take(3).collect::<Vec<_>>());
+        let bound = rng.below(12) as u64;
+        solver.set_cost_bound(Some(bound));
+        let bounded = solver.iter_solutions_by_cost()
+            .map(|(_, c)| c)
+            .collect::<Vec<_>>();
+        assert_eq!(bounded, expected.iter().copied()
+            .filter(|&c| c <= bound)
+            .collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn check_random_solution_zdds() {
+    let mut rng = SplitMix64::new(11);
+    for _ in 0..300 {
+        let problem = random_problem(&mut rng, 11, 7, false);
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        let zdd = ExactCoverSolver::new(&problem).solution_zdd();
+        assert_eq!(zdd.count(), Some(expected.len() as u128));
+        assert_eq!(sort_solutions(zdd.iter().collect()), expected);
+        for sample in zdd.samples(rng.next_u64()).take(5) {
+            assert!(expected.contains(&sort_solutions(vec![sample])[0]));
+        }
+    }
+}
+
+#[test]
+fn check_random_parallel_solutions() {
+    let mut rng = SplitMix64::new(12);
+    for _ in 0..100 {
+        let mut problem = random_problem(&mut rng, 11, 7, false);
+        let mut expected = sort_solutions(brute_force_solutions(&problem));
+
+        // Rotating the empty rows is a symmetry, after which only the
+        // solutions with the least subset of them in their class count.
+        let empty = (0..problem.rows())
+            .filter(|&r| problem.ordered_points_rows().nth(r).unwrap().next().is_none())
+            .collect::<Vec<_>>();
+        if empty.len() >= 2 && rng.below(2) == 0 {
+            let mut rows = (0..problem.rows()).collect::<Vec<_>>();
+            for (i, &r) in empty.iter().enumerate() {
+                rows[r] = empty[(i + 1) % empty.len()];
+            }
+            let symmetry = Symmetry::from_rows(&problem, rows.clone()).unwrap();
+            problem.set_symmetries(vec![symmetry]).unwrap();
+            let images = |s: &ExactCover| (1..empty.len()).map(|k| {
+                let mut image = s.0.clone();
+                for _ in 0..k {
+                    image.iter_mut().for_each(|r| *r = rows[*r]);
+                }
+                image.sort_unstable();
+                image
+            }).collect::<Vec<_>>();
+            expected.retain(|s| images(s).iter().all(|image| s.0 <= *image));
+        }
+
+        let mut solver = ParallelSolver::with_chooser(&problem, MrvBuckets::new());
+        solver.set_threads(1 + rng.below(3));
+        solver.set_split_depth(Some(rng.below(4)));
+        assert_eq!(solver.count_solutions(), Some(expected.len() as u128));
+        assert_eq!(sort_solutions(solver.iter_solutions().collect()), expected);
+    }
+}
+
+#[test]
+fn check_random_subtrees() {
+    let mut rng = SplitMix64::new(13);
+    for _ in 0..200 {
+        let problem = random_problem(&mut rng, 11, 7, false);
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        // Split once at the top, then again below each subtree, sending
+        // each path through its byte encoding.
+        let mut solver = ExactCoverSolver::new(&problem);
+        let mut actual = vec![];
+        let mut zdd_count = 0;
+        for path in solver.frontier(rng.below(3)) {
+            let path = SubtreePath::from_bytes(&path.to_bytes()).unwrap();
+            let mut solver = ExactCoverSolver::new_randomised(&problem, rng.next_u64());
+            solver.restrict_to(&path).unwrap();
+            for subpath in solver.frontier(rng.below(3)) {
+                assert_eq!(subpath.choices()[..path.depth()], *path.choices());
+                let mut solver = ExactCoverSolver::new(&problem);
+                solver.restrict_to(&subpath).unwrap();
+                assert_eq!(solver.subtree(), &subpath);
+                actual.extend(solver.iter_solutions());
+                zdd_count += solver.solution_zdd().count().unwrap();
+            }
+        }
+        assert_eq!(sort_solutions(actual), expected);
+        assert_eq!(zdd_count, expected.len() as u128);
+    }
+}
+
+#[test]
+fn check_random_checkpoints() {
+    let mut rng = SplitMix64::new(14);
+    for _ in 0..150 {
+        let mut problem = random_problem(&mut rng, 9, 6, true);
+        let costs = (0..problem.rows()).map(|_| rng.below(4) as u64).collect();
+        problem.set_costs(costs).unwrap();
+        let bound = (rng.below(2) == 0).then(|| rng.below(8) as u64);
+
+        // Checkpoint after every step, and check the resumed solver takes
+        // exactly the same steps as the original from there on.
+        let mut solver = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new());
+        solver.set_cost_bound(bound);
+        let steps = solver.iter_steps().collect::<Vec<_>>();
+        let mut solver = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new());
+        solver.set_cost_bound(bound);
+        for t in 0..=steps.len() {
+            let checkpoint = solver.checkpoint();
+            let mut resumed = ExactCoverSolver::resume_with_chooser(
+                &problem, MrvBuckets::new(), &checkpoint).unwrap();
+            assert_eq!(resumed.current_partial_solution(), solver.current_partial_solution());
+            assert_eq!(resumed.iter_steps().collect::<Vec<_>>(), steps[t..]);
+            solver.next_step();
+        }
+    }
+}
+
+#[test]
+fn check_random_forks() {
+    let mut rng = SplitMix64::new(16);
+    for _ in 0..150 {
+        let problem = random_problem(&mut rng, 9, 6, true);
+
+        // A fork takes exactly the same steps as the original from where
+        // it was forked, and exhausting it leaves the original alone.
+        let seed = rng.next_u64();
+        let steps = ExactCoverSolver::new_randomised(&problem, seed)
+            .iter_steps()
+            .collect::<Vec<_>>();
+        let mut solver = ExactCoverSolver::new_randomised(&problem, seed);
+        for t in 0..=steps.len() {
+            let mut fork = solver.fork();
+            assert_eq!(fork.current_partial_solution(), solver.current_partial_solution());
+            assert_eq!(fork.iter_steps().collect::<Vec<_>>(), steps[t..]);
+            assert_eq!(solver.next_step().as_ref(), steps.get(t));
+        }
+    }
+}
+
+#[test]
+fn check_random_step_reversal() {
+    let mut rng = SplitMix64::new(25);
+    for _ in 0..150 {
+        let mut problem = random_problem(&mut rng, 9, 6, true);
+        let costs = (0..problem.rows()).map(|_| rng.below(4) as u64).collect();
+        problem.set_costs(costs).unwrap();
+        if rng.below(3) == 0 {
+            problem.set_multiplicities(random_multiplicities(&mut rng, &problem)).unwrap();
+        }
+        let bound = (rng.below(2) == 0).then(|| rng.below(8) as u64);
+
+        // Wander back and forth through the search, checking each step
+        // undone is the one taken and leaves the solver where it was.
+        let mut solver = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new());
+        solver.set_cost_bound(bound);
+        let steps = solver.iter_steps().collect::<Vec<_>>();
+        let mut solver = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new());
+        solver.set_cost_bound(bound);
+        solver.set_record_steps(true);
+        let mut partials = vec![solver.current_partial_solution()];
+        for _ in 0..3 * steps.len() + 2 {
+            let t = partials.len() - 1;
+            if rng.below(3) == 0 {
+                let step = solver.prev_step();
+                assert_eq!(step.as_ref(), t.checked_sub(1).map(|t| &steps[t]));
+                if step.is_some() {
+                    partials.pop();
+                }
+            } else {
+                let step = solver.next_step();
+                assert_eq!(step.as_ref(), steps.get(t));
+                if step.is_some() {
+                    partials.push(solver.current_partial_solution());
+                }
+            }
+            assert_eq!(solver.current_partial_solution(), partials[partials.len() - 1]);
+        }
+        assert_eq!(solver.iter_steps().collect::<Vec<_>>(), steps[partials.len() - 1..]);
+        while solver.prev_step().is_some() {}
+        assert_eq!(solver.current_partial_solution(), partials[0]);
+        assert_eq!(solver.iter_steps().collect::<Vec<_>>(), steps);
+    }
+}
+
+#[test]
+fn check_checkpoint_errors() {
+    let mut rng = SplitMix64::new(15);
+    let problem = random_coloured_problem(&mut rng, 8, 5, 1, 2, 3);
+    let other = random_coloured_problem(&mut rng, 8, 5, 1, 2, 3);
+    let mut solver = ExactCoverSolver::new(&problem);
+    solver.next_step();
+    let checkpoint = solver.checkpoint();
+    assert!(ExactCoverSolver::resume(&problem, &checkpoint).is_ok());
+    assert_eq!(ExactCoverSolver::resume(&other, &checkpoint).err(),
+        Some(CheckpointError::DifferentProblem));
+    for i in 0..checkpoint.len() {
+        let mut corrupt = checkpoint.clone();
+        corrupt[i] ^= 0x10;
+        assert_eq!(ExactCoverSolver::resume(&problem, &corrupt).err(),
+            Some(CheckpointError::Malformed));
+    }
+    assert_eq!(ExactCoverSolver::resume(&problem, &checkpoint[1..]).err(),
+        Some(CheckpointError::Malformed));
+}
+
+// Rewrites a checkpoint with its saved state changed, keeping its
+// checksum valid.
+fn forge_checkpoint(
+    problem: &ExactCoverProblem,
+    checkpoint: &[u8],
+    forge: impl FnOnce(&mut SavedState),
+) -> Vec<u8> {
+    let mut saved = SavedState::read(checkpoint, problem.fingerprint()).unwrap();
+    forge(&mut saved);
+    saved.write(problem.fingerprint())
+}
+
+#[test]
+fn check_forged_checkpoints() {
+    let problem = KnuthSimple.spec();
+    let mut solver = ExactCoverSolver::new(&problem);
+    assert!(matches!(solver.next_step(), Some(SolverStep::SelectColumn { .. })));
+    let Some(SolverStep::PushRow(row)) = solver.next_step() else { panic!() };
+    let checkpoint = solver.checkpoint();
+    let resumed = forge_checkpoint(&problem, &checkpoint, |_| ());
+    assert_eq!(resumed, checkpoint);
+
+    // Covering the chosen column twice.
+    let twice = forge_checkpoint(&problem, &checkpoint, |saved| {
+        assert_eq!(saved.stack[0].0, tag::AFTER_ADD_OR_REPLACE_ROW);
+        saved.stack.insert(1, saved.stack[0]);
+    });
+    assert_eq!(ExactCoverSolver::resume(&problem, &twice).err(),
+        Some(CheckpointError::Malformed));
+
+    // Pushing a row that has been forbidden.
+    let forbidden = forge_checkpoint(&problem, &checkpoint, |saved| {
+        saved.forbidden.push(row);
+    });
+    assert_eq!(ExactCoverSolver::resume(&problem, &forbidden).err(),
+        Some(CheckpointError::Malformed));
+
+    // A path to a subtree that isn't pinned below the floor, whether or
+    // not its column exists, or a floor over a level that isn't pinned.
+    for path in [vec![(99, 0)], vec![(0, row)]] {
+        let unpinned = forge_checkpoint(&problem, &checkpoint, |saved| {
+            saved.subtree = path;
+        });
+        assert_eq!(ExactCoverSolver::resume(&problem, &unpinned).err(),
+            Some(CheckpointError::Malformed));
+    }
+    let floor = forge_checkpoint(&problem, &checkpoint, |saved| {
+        saved.floor = 1;
+    });
+    assert_eq!(ExactCoverSolver::resume(&problem, &floor).err(),
+        Some(CheckpointError::Malformed));
+
+    // A cost other than that of the rows pushed, which would otherwise
+    // underflow once they are popped.
+    let mut costed = KnuthSimple.spec();
+    costed.set_costs(vec![1; costed.rows()]).unwrap();
+    let mut solver = ExactCoverSolver::new(&costed);
+    solver.next_step();
+    solver.next_step();
+    let checkpoint = solver.checkpoint();
+    let cheap = forge_checkpoint(&costed, &checkpoint, |saved| {
+        assert_eq!(saved.cost, 1);
+        saved.cost = 0;
+    });
+    assert_eq!(ExactCoverSolver::resume(&costed, &cheap).err(),
+        Some(CheckpointError::Malformed));
+}
+
+#[test]
+fn check_random_tree_size_estimates() {
+    let mut rng = SplitMix64::new(18);
+    for _ in 0..100 {
+        let mut problem = random_problem(&mut rng, 12, 7, false);
+        let bound = match rng.below(2) {
+            0 => None,
+            _ => {
+                let costs = (0..problem.rows()).map(|_| rng.below(10) as u64).collect();
+                problem.set_costs(costs).unwrap();
+                Some(rng.below(25) as u64)
+            },
+        };
+
+        // The exact size of the search.
+        let mut solver = ExactCoverSolver::new(&problem);
+        solver.set_cost_bound(bound);
+        let (mut nodes, mut solutions) = (1, 0);
+        for step in solver.iter_steps() {
+            match step {
+                SolverStep::PushRow(_) | SolverStep::AdvanceRow(..) => nodes += 1,
+                SolverStep::ReportSolution(_) => solutions += 1,
+                _ => (),
+            }
+        }
+        #[cfg(feature = "stats")]
+        let mems = solver.mems();
+
+        // The estimates are unbiased, so should be within a few standard
+        // errors of the truth, and exact on trees without any choices.
+        let mut solver = ExactCoverSolver::new(&problem);
+        solver.set_cost_bound(bound);
+        let estimate = solver.estimate_tree_size(2000, rng.next_u64());
+        #[allow(unused_mut)]
+        let mut checks = vec![(estimate.nodes, nodes), (estimate.solutions, solutions)];
+        #[cfg(feature = "stats")]
+        checks.push((estimate.mems, mems));
+        for (e, exact) in checks {
+            assert_eq!(e.samples, 2000);
+            let tolerance = 5.0 * e.standard_error() + 1e-9 * exact as f64;
+            assert!((e.mean - exact as f64).abs() <= tolerance,
+                "estimated {e:?}, exactly {exact}");
+        }
+        assert_eq!(solver.iter_solutions().count(), solutions as usize);
+    }
+}
+
+#[test]
+fn check_random_reductions() {
+    let mut rng = SplitMix64::new(20);
+    for _ in 0..500 {
+        let mut problem = random_problem(&mut rng, 11, 7, false);
+        let costs = (0..problem.rows()).map(|_| rng.below(10) as u64).collect();
+        problem.set_costs(costs).unwrap();
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        // Lifting the solutions of the reduced problem gives back exactly
+        // the solutions of the original, at the same costs.
+        let (reduced, reduction) = problem.reduce();
+        assert!(reduced.rows() <= problem.rows());
+        assert!(reduced.columns() <= problem.columns());
+        let forced_cost = reduction.forced_rows().iter()
+            .map(|&r| problem.cost(r))
+            .sum::<u64>();
+        let mut lifted = vec![];
+        for solution in ExactCoverSolver::new(&reduced).iter_solutions() {
+            let lift = reduction.lift(&solution);
+            assert_eq!(problem.solution_cost(&lift), reduced.solution_cost(&solution) + forced_cost);
+            lifted.push(lift);
+        }
+        assert_eq!(sort_solutions(lifted), expected);
+
+        for r in 0..reduced.rows() {
+            let original = reduction.original_row(r);
+            assert!(reduced.ordered_points_rows().nth(r).unwrap()
+                .map(|j| reduction.original_column(j))
+                .all(|j| problem.ordered_points_rows().nth(original).unwrap().any(|k| k == j)));
+        }
+    }
+}
+
+#[test]
+fn check_random_decompositions() {
+    let mut rng = SplitMix64::new(21);
+    for _ in 0..300 {
+        // Sparse problems, so that they often fall apart.
+        let problem = random_sparse_problem(&mut rng, 11, 9, 5, true);
+        let expected = sort_solutions(brute_force_solutions(&problem));
+
+        let decomposition = problem.decompose();
+        let actual = decomposition.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), expected);
+        assert_eq!(decomposition.count_solutions(), Some(expected.len() as u128));
+
+        // The components share no rows or columns between them.
+        let mut row_seen = vec![false; problem.rows()];
+        let mut col_seen = vec![false; problem.columns()];
+        for component in decomposition.components() {
+            let sub = component.problem();
+            for r in 0..sub.rows() {
+                assert!(!std::mem::replace(&mut row_seen[component.original_row(r)], true));
+            }
+            for j in 0..sub.columns() {
+                assert!(!std::mem::replace(&mut col_seen[component.original_column(j)], true));
+            }
+        }
+        assert!(row_seen.iter().all(|&seen| seen));
+    }
+}
+
+#[test]
+fn check_random_symmetries() {
+    let mut rng = SplitMix64::new(22);
+    for _ in 0..200 {
+        // Copies of a random problem side by side, with the symmetry
+        // that shifts each copy to the next.
+        let copies = 2 + rng.below(2);
+        let num_rows = rng.below(if copies == 2 { 6 } else { 5 });
+        let num_cols = rng.below(5);
+        let num_secondary = rng.below(num_cols + 1);
+        let primary = num_cols - num_secondary;
+        let base = random_coloured_problem(
+            &mut rng, num_rows, num_cols, num_secondary, 2, 3);
+        let rows = (0..copies).flat_map(|t| base.ordered_coloured_rows()
+            .map(move |row| row.map(move |(j, colour)| match j < primary {
+                true => (t*primary + j, colour),
+                false => (copies*primary + t*num_secondary + j - primary, colour),
+            })));
+        let mut problem = ExactCoverProblem::new_coloured(
+            rows, copies*num_cols, copies*num_secondary).unwrap();
+        let shift = (0..copies*num_rows)
+            .map(|r| (r + num_rows) % (copies*num_rows))
+            .collect();
+        let symmetry = Symmetry::from_rows(&problem, shift).unwrap();
+        problem.set_symmetries(vec![symmetry]).unwrap();
+
+        let all = brute_force_solutions(&problem);
+        let image = |rows: &[usize], k: usize| {
+            let mut image = rows.iter()
+                .map(|&r| (r + k*num_rows) % (copies*num_rows))
+                .collect::<Vec<_>>();
+            image.sort_unstable();
+            image
+        };
+        let expected = all.iter()
+            .filter(|s| (1..copies).all(|k| s.0 <= image(&s.0, k)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut solver = ExactCoverSolver::new(&problem);
+        let actual = solver.iter_solutions().collect::<Vec<_>>();
+        let orbits = actual.iter().map(|s| solver.orbit_size(s)).sum::<usize>();
+        assert_eq!(sort_solutions(actual), sort_solutions(expected));
+        assert_eq!(orbits, all.len());
+    }
+}
+
+#[test]
+fn check_random_assumptions() {
+    let mut rng = SplitMix64::new(23);
+    for _ in 0..200 {
+        let mut problem = random_problem(&mut rng, 10, 6, false);
+        let costs = (0..problem.rows()).map(|_| rng.below(4) as u64).collect();
+        problem.set_costs(costs).unwrap();
+        let all = brute_force_solutions(&problem);
+        let empty = (0..problem.rows())
+            .filter(|&r| problem.ordered_points_rows().nth(r).unwrap().next().is_none())
+            .collect::<Vec<_>>();
+
+        // The same solver is reused for several sets of assumptions, and
+        // must give back every solution once they are lifted.
+        let mut solver = ExactCoverSolver::with_chooser(&problem, MrvBuckets::new());
+        for _ in 0..4 {
+            let mut forced = vec![];
+            let mut forbidden = vec![];
+            for r in (0..problem.rows()).filter(|r| !empty.contains(r)) {
+                match rng.below(6) {
+                    0 => forced.push(r),
+                    1 | 2 => forbidden.push(r),
+                    _ => (),
+                }
+            }
+            let expected = all.iter()
+                .filter(|s| forced.iter().all(|r| s.0.contains(r))
+                    && !forbidden.iter().any(|r| s.0.contains(r)))
+                .cloned()
+                .collect::<Vec<_>>();
+            match solver.reset_with_assumptions(&forced, &forbidden) {
+                Ok(()) => {
+                    assert_eq!(solver.forced_rows(), forced);
+                    // A checkpoint part way through carries the assumptions.
+                    let steps = solver.iter_steps().collect::<Vec<_>>();
+                    solver.reset_with_assumptions(&forced, &forbidden).unwrap();
+                    for _ in 0..rng.below(steps.len() + 1) {
+                        solver.next_step();
+                    }
+                    let mut resumed = ExactCoverSolver::resume_with_chooser(
+                        &problem, MrvBuckets::new(), &solver.checkpoint()).unwrap();
+                    assert_eq!(resumed.iter_steps().collect::<Vec<_>>(),
+                        solver.iter_steps().collect::<Vec<_>>());
+
+                    let zdd = solver.solution_zdd();
+                    assert_eq!(sort_solutions(zdd.iter().collect()), sort_solutions(expected.clone()));
+                    solver.reset_with_assumptions(&forced, &forbidden).unwrap();
+                    let actual = solver.iter_solutions().collect::<Vec<_>>();
+                    assert_eq!(sort_solutions(actual), sort_solutions(expected));
+                },
+                Err(AssumptionError::Contradiction { row_idx }) => {
+                    assert!(forced.contains(&row_idx));
+                    assert!(expected.is_empty());
+                    assert!(solver.forced_rows().is_empty());
+                },
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        }
+        solver.reset_with_assumptions(&[], &[]).unwrap();
+        let actual = solver.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(sort_solutions(actual), sort_solutions(all));
+    }
+}
+
+#[test]
+fn check_random_infeasibility_explanations() {
+    let mut rng = SplitMix64::new(24);
+    for _ in 0..300 {
+        let problem = random_problem(&mut rng, 9, 7, true);
+        let solutions = brute_force_solutions(&problem);
+        let Some(why) = problem.explain_infeasibility() else {
+            assert!(!solutions.is_empty());
+            continue;
+        };
+        assert!(solutions.is_empty());
+        let rows = problem.ordered_points_rows()
+            .map(|row| row.collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for &c in why.empty_columns() {
+            let count = rows.iter().filter(|row| row.contains(&c)).count();
+            assert!(count < problem.multiplicity(c).0);
+        }
+        for &c in why.uncoverable_columns() {
+            assert!(rows.iter().any(|row| row.contains(&c)));
+        }
+
+        // The core can't be covered, but can without any one column.
+        let core = why.core_columns();
+        let restricted = |columns: &[usize]| {
+            let primary = columns.iter()
+                .filter(|&&j| j < problem.primary_columns())
+                .count();
+            let mut sub = ExactCoverProblem::new_coloured(
+                problem.ordered_coloured_rows()
+                    .map(|row| row
+                        .filter_map(|(j, colour)| columns.iter()
+                            .position(|&k| k == j)
+                            .map(|i| (i, colour)))
+                        .collect::<Vec<_>>())
+                    .filter(|row| !row.is_empty())
+                    .map(|row| row.into_iter()),
+                columns.len(), columns.len() - primary).unwrap();
+            if problem.has_multiplicities() {
+                let multiplicities = columns[..primary].iter()
+                    .map(|&j| problem.multiplicity(j))
+                    .collect();
+                sub.set_multiplicities(multiplicities).unwrap();
+            }
+            sub
+        };
+        assert!(brute_force_solutions(&restricted(core)).is_empty());
+        for i in 0..core.len() {
+            let mut fewer = core.to_vec();
+            fewer.remove(i);
+            assert!(!brute_force_solutions(&restricted(&fewer)).is_empty());
+        }
+        let core_rows = (0..problem.rows())
+            .filter(|&r| rows[r].iter().any(|j| core.contains(j)))
+            .collect::<Vec<_>>();
+        assert_eq!(why.core_rows(), core_rows);
+    }
+}
@@ -3,26 +3,54 @@
 //! live in the `problems` module. This is because their generation
 //! TODO: have some simple test _step_ cases for fine-grained detail.
 
-use crate::{
-    problems::NQueens,
-    solver::{ExactCover, ExactCoverSolver, ExactCoverRepresentable,
-        ExactCoverProblem, SparseBinaryMatrix}};
+use std::thread;
+use std::time::Instant;
+
+use crate::problems::NQueens;
+use crate::solver::{
+    AssumptionError, ColumnChooser, ExactCover, ExactCoverSolver, ExactCoverProblem,
+    ExactCoverProblemError, ExactCoverRepresentable, FirstColumn, MrvBuckets, MrvRandomTies,
+    ParallelSolver, Priority, SearchBudget, SearchOutcome, SolverStep,
+    SolverHandle, SparseBinaryMatrix, SubtreeError, SubtreePath, Symmetry,
+    count_solutions_parallel,
+};
 
 pub trait TestCase {
     fn spec(&self) -> ExactCoverProblem;
     fn expected_solutions(&self) -> Vec<ExactCover>;
     fn assert_solution_match(&self) {
-        let exp = self.expected_solutions();
+        let exp = sort_solutions(self.expected_solutions());
         let spec = self.spec();
-        let actual_sols = ExactCoverSolver::new(&spec)
+        let actual_sols = sort_solutions(ExactCoverSolver::new(&spec)
             .iter_solutions()
-            .map(|mut s| { s.0.sort_unstable(); s })
-            .collect::<Vec<_>>();
-        // TODO: add solution sorting helper. As it stands this will blow up
+            .collect::<Vec<_>>());
         assert_eq!(exp, actual_sols);
     }
 }
 
+/// As `TestCase::assert_solution_match`, but with a given column chooser.
+pub fn assert_solution_match_with(
+    case: &dyn TestCase, chooser: impl ColumnChooser,
+) {
+    let exp = sort_solutions(case.expected_solutions());
+    let spec = case.spec();
+    let actual_sols = sort_solutions(
+        ExactCoverSolver::with_chooser(&spec, chooser)
+            .iter_solutions()
+            .collect::<Vec<_>>());
+    assert_eq!(exp, actual_sols);
+}
+
+/// Sorts the rows of each solution, then the list of solutions itself,
+/// so that solution sets can be compared independent of search order.
+pub fn sort_solutions(mut solutions: Vec<ExactCover>) -> Vec<ExactCover> {
+    for s in solutions.iter_mut() {
+        s.0.sort_unstable();
+    }
+    solutions.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    solutions
+}
+
 pub struct KnuthSimple;
 
 impl TestCase for KnuthSimple {
@@ -93,7 +121,7 @@ pub struct ThreeRowsZeroCols;
 impl TestCase for ThreeRowsZeroCols {
     fn spec(&self) -> ExactCoverProblem {
         let matrix = SparseBinaryMatrix::from_array_2d::<3, 0>([[],[],[]]);
-        ExactCoverProblem::new_general(matrix, 3).unwrap()
+        ExactCoverProblem::new_general(matrix, 0).unwrap()
     }
 
     fn expected_solutions(&self) -> Vec<ExactCover> {
@@ -106,6 +134,170 @@ impl TestCase for ThreeRowsZeroCols {
     }
 }
 
+pub struct KnuthSimpleWithEmptyRows;
+
+impl TestCase for KnuthSimpleWithEmptyRows {
+    fn spec(&self) -> ExactCoverProblem {
+        let o = false; let x = true;
+        let arrays = [
+            [o,o,o,o,o,o,o],
+            [o,o,x,o,x,x,o],
+            [x,o,o,x,o,o,x],
+            [o,x,x,o,o,x,o],
+            [o,o,o,o,o,o,o],
+            [x,o,o,x,o,o,o],
+            [o,x,o,o,o,o,x],
+            [o,o,o,x,x,o,x],
+        ];
+        let matrix = SparseBinaryMatrix::from_array_2d(arrays);
+        ExactCoverProblem::new_general(matrix, 0).unwrap()
+    }
+
+    fn expected_solutions(&self) -> Vec<ExactCover> {
+        [vec![1,5,6], vec![0,1,5,6], vec![1,4,5,6], vec![0,1,4,5,6]]
+            .into_iter()
+            .map(ExactCover)
+            .collect::<Vec<_>>()
+    }
+}
+
+pub struct AllSecondaryRows;
+
+impl TestCase for AllSecondaryRows {
+    fn spec(&self) -> ExactCoverProblem {
+        let o = false; let x = true;
+        let arrays = [
+            [x,o,o],
+            [o,x,o],
+            [x,x,o],
+            [o,o,x],
+        ];
+        let matrix = SparseBinaryMatrix::from_array_2d(arrays);
+        ExactCoverProblem::new_general(matrix, 3).unwrap()
+    }
+
+    // Every subset of rows that do not share a column.
+    fn expected_solutions(&self) -> Vec<ExactCover> {
+        [vec![], vec![0], vec![1], vec![2], vec![3], vec![0,1],
+            vec![0,3], vec![1,3], vec![2,3], vec![0,1,3]]
+            .into_iter()
+            .map(ExactCover)
+            .collect::<Vec<_>>()
+    }
+}
+
+pub struct MixedOptionalAndEmptyRows;
+
+impl TestCase for MixedOptionalAndEmptyRows {
+    // Columns 0 and 1 are primary; 2 and 3 are secondary.
+    fn spec(&self) -> ExactCoverProblem {
+        let o = false; let x = true;
+        let arrays = [
+            [x,o,x,o],
+            [o,x,o,o],
+            [o,x,o,x],
+            [o,o,x,o],
+            [o,o,o,x],
+            [o,o,x,x],
+            [o,o,o,o],
+        ];
+        let matrix = SparseBinaryMatrix::from_array_2d(arrays);
+        ExactCoverProblem::new_general(matrix, 2).unwrap()
+    }
+
+    fn expected_solutions(&self) -> Vec<ExactCover> {
+        [vec![0,1], vec![0,1,4], vec![0,2],
+            vec![0,1,6], vec![0,1,4,6], vec![0,2,6]]
+            .into_iter()
+            .map(ExactCover)
+            .collect::<Vec<_>>()
+    }
+}
+
+pub struct KnuthColoured;
+
+impl TestCase for KnuthColoured {
+    // The example of TAOCP 7.2.2.1 (49). Columns p, q, r are primary and
+    // x, y secondary; colours A and B are 1 and 2.
+    fn spec(&self) -> ExactCoverProblem {
+        let rows = [
+            vec![(0,0), (1,0), (3,0), (4,1)],
+            vec![(0,0), (2,0), (3,1), (4,0)],
+            vec![(0,0), (3,2)],
+            vec![(1,0), (3,1)],
+            vec![(2,0), (4,2)],
+        ];
+        ExactCoverProblem::new_coloured(
+            rows.into_iter().map(|r| r.into_iter()), 5, 2).unwrap()
+    }
+
+    fn expected_solutions(&self) -> Vec<ExactCover> {
+        vec![ExactCover(vec![1,3])]
+    }
+}
+
+pub struct ColouredOptionalRows;
+
+impl TestCase for ColouredOptionalRows {
+    // Column 0 is primary; 1 and 2 are secondary.
+    fn spec(&self) -> ExactCoverProblem {
+        let rows = [
+            vec![(0,0), (1,1)],
+            vec![(1,1)],
+            vec![(1,2)],
+            vec![(1,1), (2,1)],
+            vec![(2,0)],
+            vec![(0,0)],
+        ];
+        ExactCoverProblem::new_coloured(
+            rows.into_iter().map(|r| r.into_iter()), 3, 2).unwrap()
+    }
+
+    fn expected_solutions(&self) -> Vec<ExactCover> {
+        [vec![0], vec![0,1], vec![0,3], vec![0,4], vec![0,1,3], vec![0,1,4],
+            vec![5], vec![1,5], vec![2,5], vec![3,5], vec![4,5],
+            vec![1,3,5], vec![1,4,5], vec![2,4,5]]
+            .into_iter()
+            .map(ExactCover)
+            .collect::<Vec<_>>()
+    }
+}
+
+#[test]
+fn check_core_solutions_only_with_empty_rows() {
+    let spec = KnuthSimpleWithEmptyRows.spec();
+    let mut solver = ExactCoverSolver::new(&spec);
+    solver.set_expand_empty_rows(false);
+    assert_eq!(solver.empty_rows(), &[0, 4]);
+    assert_eq!(solver.empty_row_multiplier(), Some(4));
+    let solutions = solver.iter_solutions()
+        .map(|mut s| { s.0.sort_unstable(); s })
+        .collect::<Vec<_>>();
+    assert_eq!(solutions, vec![ExactCover(vec![1,5,6])]);
+}
+
+#[test]
+fn check_memory_reqs_cover_footprint() {
+    for case in [&MixedOptionalAndEmptyRows as &dyn TestCase, &KnuthSimpleWithEmptyRows,
+        &ColouredOptionalRows] {
+        let spec = case.spec();
+        let reqs = ExactCoverSolver::memory_reqs(&spec);
+        let mut solver = ExactCoverSolver::new(&spec);
+        assert_eq!(reqs, solver.memory_footprint());
+        while solver.next_step().is_some() {
+            assert!(reqs >= solver.memory_footprint());
+        }
+    }
+
+    // The symmetry group is counted too.
+    let q = NQueens::new(6);
+    let mut spec = q.exact_cover_problem();
+    let reqs = ExactCoverSolver::memory_reqs(&spec);
+    spec.set_symmetries(q.symmetries()).unwrap();
+    assert!(ExactCoverSolver::memory_reqs(&spec) > reqs);
+    assert_eq!(ExactCoverSolver::memory_reqs(&spec), ExactCoverSolver::new(&spec).memory_footprint());
+}
+
 // TODO: consider writing a proc attribute macro to generate these on the fly.
 // This is especially interesting for when we want to product all tests
 // with all invariants.
@@ -115,3 +307,685 @@ impl TestCase for ThreeRowsZeroCols {
 #[test] fn check_solutions_zero_rows_three_cols() { ZeroRowsThreeCols.assert_solution_match(); }
 #[test] fn check_solutions_zero_rows_three_cols_all_secondary() { ZeroRowsThreeColsAllSecondary.assert_solution_match(); }
 #[test] fn check_solutions_three_rows_zero_cols()      { ThreeRowsZeroCols.assert_solution_match(); }
+#[test] fn check_solutions_knuth_simple_with_empty_rows() { KnuthSimpleWithEmptyRows.assert_solution_match(); }
+#[test] fn check_solutions_all_secondary_rows() { AllSecondaryRows.assert_solution_match(); }
+#[test] fn check_solutions_mixed_optional_and_empty_rows() { MixedOptionalAndEmptyRows.assert_solution_match(); }
+#[test] fn check_solutions_knuth_coloured() { KnuthColoured.assert_solution_match(); }
+#[test] fn check_solutions_coloured_optional_rows() { ColouredOptionalRows.assert_solution_match(); }
+
+#[test]
+fn check_solution_zdds() {
+    for case in [&KnuthSimple as &dyn TestCase, &ZeroByZero, &ZeroRowsThreeCols,
+        &ZeroRowsThreeColsAllSecondary, &ThreeRowsZeroCols,
+        &KnuthSimpleWithEmptyRows, &AllSecondaryRows, &MixedOptionalAndEmptyRows,
+        &KnuthColoured, &ColouredOptionalRows] {
+        let expected = sort_solutions(case.expected_solutions());
+        let zdd = ExactCoverSolver::new(&case.spec()).solution_zdd();
+        assert_eq!(zdd.count(), Some(expected.len() as u128));
+        assert_eq!(sort_solutions(zdd.iter().collect()), expected);
+    }
+}
+
+#[test]
+fn check_solution_zdd_queens() {
+    let counts = [1, 1, 0, 0, 2, 10, 4, 40, 92];
+    for (n, &count) in counts.iter().enumerate() {
+        let problem = NQueens::new(n).exact_cover_problem();
+        let zdd = ExactCoverSolver::new(&problem).solution_zdd();
+        assert_eq!(zdd.count(), Some(count));
+        assert_eq!(zdd.iter().count() as u128, count);
+    }
+}
+
+#[test]
+fn check_solution_zdd_samples_uniform() {
+    let problem = NQueens::new(6).exact_cover_problem();
+    let zdd = ExactCoverSolver::new(&problem).solution_zdd();
+    let solutions = sort_solutions(zdd.iter().collect());
+    let mut hits = vec![0; solutions.len()];
+    for sample in zdd.samples(3).take(4000) {
+        let sample = sort_solutions(vec![sample]).remove(0);
+        hits[solutions.binary_search_by(|s| s.0.cmp(&sample.0)).unwrap()] += 1;
+    }
+    // Each of the four solutions is expected 1000 times.
+    assert!(hits.iter().all(|&h| (850..1150).contains(&h)), "{hits:?}");
+
+    let problem = NQueens::new(3).exact_cover_problem();
+    let zdd = ExactCoverSolver::new(&problem).solution_zdd();
+    assert_eq!(zdd.samples(3).next(), None);
+}
+
+#[test]
+fn check_parallel_queens() {
+    let counts = [1, 1, 0, 0, 2, 10, 4, 40, 92];
+    for (n, &count) in counts.iter().enumerate() {
+        let problem = NQueens::new(n).exact_cover_problem();
+        for threads in [1, 2, 4] {
+            assert_eq!(count_solutions_parallel(&problem, threads), Some(count));
+            let mut solver = ParallelSolver::new(&problem);
+            solver.set_threads(threads);
+            assert_eq!(solver.iter_solutions().count() as u128, count);
+        }
+    }
+
+    // Dropping the iterator early stops the search.
+    let problem = NQueens::new(10).exact_cover_problem();
+    assert_eq!(ParallelSolver::new(&problem).iter_solutions().take(3).count(), 3);
+}
+
+#[test]
+fn check_restricted_subtrees() {
+    let spec = KnuthSimple.spec();
+    let mut solver = ExactCoverSolver::new(&spec);
+    // Column 0 is covered by rows 1 and 3, and only row 3 leads anywhere.
+    let paths = solver.frontier(1);
+    assert_eq!(paths, [
+        SubtreePath::new(vec![(0, 1)]), SubtreePath::new(vec![(0, 3)]),
+    ]);
+    solver.restrict_to(&paths[0]).unwrap();
+    assert_eq!(solver.current_partial_solution().0, [1]);
+    assert_eq!(solver.next_solution(), None);
+    solver.restrict_to(&paths[1]).unwrap();
+    let solution = solver.next_solution().unwrap();
+    assert_eq!(sort_solutions(vec![solution]), [ExactCover(vec![0, 3, 4])]);
+    assert_eq!(solver.next_solution(), None);
+
+    // Restrictions survive checkpoints.
+    solver.restrict_to(&paths[1]).unwrap();
+    let mut resumed = ExactCoverSolver::resume(&spec, &solver.checkpoint()).unwrap();
+    assert_eq!(resumed.subtree(), &paths[1]);
+    assert_eq!(resumed.iter_steps().collect::<Vec<_>>(),
+        solver.iter_steps().collect::<Vec<_>>());
+
+    // Restarts stay within the subtree.
+    let mut solver = ExactCoverSolver::new_randomised(&spec, 1);
+    solver.set_restart_after(Some(1));
+    solver.restrict_to(&paths[1]).unwrap();
+    for step in solver.iter_steps().take(50) {
+        assert!(!matches!(step, SolverStep::PopRow(3)));
+    }
+
+    let bad = [
+        (vec![(7, 0)], SubtreeError::UnavailableColumn { depth: 0, col_idx: 7 }),
+        (vec![(0, 0)], SubtreeError::UnavailableRow { depth: 0, row_idx: 0 }),
+        (vec![(0, 3), (0, 1)], SubtreeError::UnavailableColumn { depth: 1, col_idx: 0 }),
+        (vec![(0, 3), (1, 1)], SubtreeError::UnavailableRow { depth: 1, row_idx: 1 }),
+    ];
+    for (choices, error) in bad {
+        assert_eq!(solver.restrict_to(&SubtreePath::new(choices)), Err(error));
+        assert_eq!(solver.subtree(), &SubtreePath::root());
+    }
+}
+
+#[test]
+fn check_search_budgets() {
+    let spec = NQueens::new(6).exact_cover_problem();
+    let expected = ExactCoverSolver::new(&spec).iter_solutions().collect::<Vec<_>>();
+
+    // A step at a time, the search finds the same solutions in order.
+    let mut solver = ExactCoverSolver::new(&spec);
+    let mut solutions = vec![];
+    let mut pauses = 0;
+    loop {
+        let mut budget = SearchBudget::steps(1);
+        match solver.next_solution_within(&mut budget) {
+            SearchOutcome::Solution(s) => solutions.push(s),
+            SearchOutcome::BudgetExhausted => pauses += 1,
+            SearchOutcome::Finished => break,
+            SearchOutcome::Cancelled => unreachable!(),
+        }
+        assert_eq!(budget.steps, Some(0));
+    }
+    assert_eq!(solutions, expected);
+    assert!(pauses > 100);
+
+    let mut solver = ExactCoverSolver::new(&spec);
+    let mut budget = SearchBudget::solutions(2);
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::Solution(expected[0].clone()));
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::Solution(expected[1].clone()));
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::BudgetExhausted);
+    assert_eq!(solver.next_solution(), Some(expected[2].clone()));
+
+    // Five nodes in, the search stops where it pushes or advances to its
+    // fifth row.
+    let mut solver = ExactCoverSolver::new(&spec);
+    let mut budget = SearchBudget::nodes(5).with_solutions(1);
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::BudgetExhausted);
+    assert_eq!((budget.nodes, budget.solutions), (Some(0), Some(1)));
+    let mut reference = ExactCoverSolver::new(&spec);
+    let mut nodes = 0;
+    while nodes < 5 {
+        if let Some(SolverStep::PushRow(_) | SolverStep::AdvanceRow(..)) = reference.next_step() {
+            nodes += 1;
+        }
+    }
+    assert_eq!(solver.current_partial_solution(), reference.current_partial_solution());
+    assert_eq!(solver.next_step(), reference.next_step());
+
+    let mut solver = ExactCoverSolver::new(&spec);
+    #[cfg(feature = "stats")]
+    {
+        let mut budget = SearchBudget::mems(1000);
+        assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::BudgetExhausted);
+        assert_eq!(budget.mems, Some(0));
+        assert!(solver.mems() >= 1000);
+    }
+    let mut budget = SearchBudget::unlimited();
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::Solution(expected[0].clone()));
+
+    let mut budget = SearchBudget::deadline(Instant::now());
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::BudgetExhausted);
+    assert_eq!(solver.next_solution(), Some(expected[1].clone()));
+
+    let spec = NQueens::new(3).exact_cover_problem();
+    let mut solver = ExactCoverSolver::new(&spec);
+    let mut budget = SearchBudget::steps(1000);
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::Finished);
+}
+
+#[test]
+fn check_solver_handles() {
+    let spec = NQueens::new(8).exact_cover_problem();
+    let mut solver = ExactCoverSolver::new(&spec);
+    let handle = solver.handle();
+    assert_eq!((handle.steps(), handle.solutions()), (0, 0));
+
+    // Progress is visible from another thread as the search goes on.
+    let mut steps = 0;
+    let mut fraction = 0.0;
+    while let Some(step) = solver.next_step() {
+        steps += 1;
+        let (theirs, solutions) = (handle.clone(), handle.solutions());
+        thread::spawn(move || {
+            assert_eq!(theirs.steps(), steps);
+            assert_eq!(theirs.solutions(), solutions);
+        }).join().unwrap();
+        if let SolverStep::SelectColumn { .. } = step {
+            assert!(handle.depth() > 0);
+        }
+        assert!(handle.fraction_explored() >= fraction);
+        fraction = handle.fraction_explored();
+        assert!(fraction < 1.0);
+    }
+    assert_eq!(handle.solutions(), 92);
+    assert_eq!(handle.fraction_explored(), 1.0);
+
+    // A fork starts without handles.
+    let mut solver = ExactCoverSolver::new(&spec);
+    let handle = solver.handle();
+    solver.next_solution();
+    let mut fork = solver.fork();
+    assert_eq!(fork.iter_solutions().count(), 91);
+    assert_eq!(handle.solutions(), 1);
+
+    // Cancelling from another thread stops the search.
+    let spec = NQueens::new(12).exact_cover_problem();
+    let mut solver = ExactCoverSolver::new(&spec);
+    let handle = solver.handle();
+    let canceller = {
+        let handle: SolverHandle = handle.clone();
+        thread::spawn(move || {
+            while handle.solutions() < 10 {
+                thread::yield_now();
+            }
+            handle.cancel();
+        })
+    };
+    let found = solver.iter_solutions().count();
+    canceller.join().unwrap();
+    assert!(solver.is_cancelled());
+    assert!((10..14200).contains(&found));
+    assert_eq!(handle.solutions(), found as u64);
+    assert_eq!(solver.next_step(), None);
+    let mut budget = SearchBudget::unlimited();
+    assert_eq!(solver.next_solution_within(&mut budget), SearchOutcome::Cancelled);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn check_solver_stats() {
+    let spec = NQueens::new(8).exact_cover_problem();
+    let mut solver = ExactCoverSolver::new(&spec);
+    let (mut nodes, mut branchings) = (1, 0);
+    for step in solver.iter_steps() {
+        match step {
+            SolverStep::PushRow(_) | SolverStep::AdvanceRow(..) => nodes += 1,
+            SolverStep::SelectColumn { .. } => branchings += 1,
+            _ => (),
+        }
+    }
+    let stats = solver.stats();
+    assert_eq!(stats.mems, solver.mems());
+    assert_eq!(stats.nodes(), nodes);
+    assert_eq!(stats.nodes_per_depth[0], 1);
+    assert_eq!(stats.nodes_per_depth.len(), 9);
+    assert_eq!(stats.branching.iter().sum::<u64>(), branchings);
+    assert_eq!(stats.solutions_per_depth, [0, 0, 0, 0, 0, 0, 0, 0, 92]);
+    assert_eq!(stats.max_depth, 8);
+
+    solver.reset_stats();
+    assert_eq!(solver.stats(), Default::default());
+
+    // A solution's depth is its number of rows other than empty rows,
+    // whether or not some of them are optional.
+    let mut solver = ExactCoverSolver::new(&MixedOptionalAndEmptyRows.spec());
+    let mut expected = vec![];
+    for solution in solver.iter_solutions().collect::<Vec<_>>() {
+        let depth = solution.0.iter()
+            .filter(|r| !solver.empty_rows().contains(r))
+            .count();
+        expected.resize(expected.len().max(depth + 1), 0);
+        expected[depth] += 1;
+    }
+    assert_eq!(solver.stats().solutions_per_depth, expected);
+}
+
+#[test]
+fn check_reduction() {
+    let rows = [
+        vec![0, 1],
+        vec![0, 1, 2],
+        vec![2, 4],
+        vec![3],
+        vec![1, 2],
+        vec![1, 4],
+        vec![0, 1, 4],
+    ];
+    let spec = ExactCoverProblem::new(
+        rows.iter().map(|r| r.iter().copied()), 5, 1).unwrap();
+    let (reduced, reduction) = spec.reduce();
+
+    // Row 3 is the only one for column 3. Column 1 has every row of
+    // column 0, so rows 4 and 5 can't be chosen and column 1 is
+    // redundant. Then row 6 clashes with both rows left for column 2.
+    assert_eq!(reduction.forced_rows(), [3]);
+    assert_eq!((0..reduced.rows()).map(|r| reduction.original_row(r)).collect::<Vec<_>>(),
+        [0, 1, 2]);
+    assert_eq!((0..reduced.columns()).map(|c| reduction.original_column(c)).collect::<Vec<_>>(),
+        [0, 2, 4]);
+    assert_eq!((reduced.primary_columns(), reduced.secondary_columns()), (2, 1));
+
+    let lifted = ExactCoverSolver::new(&reduced).iter_solutions()
+        .map(|s| reduction.lift(&s))
+        .collect::<Vec<_>>();
+    assert_eq!(sort_solutions(lifted), [ExactCover(vec![0, 2, 3]), ExactCover(vec![1, 3])]);
+    let expected = ExactCoverSolver::new(&spec).iter_solutions().collect::<Vec<_>>();
+    assert_eq!(sort_solutions(expected), [ExactCover(vec![0, 2, 3]), ExactCover(vec![1, 3])]);
+
+    // Queens have nothing to reduce.
+    let queens = NQueens::new(6).exact_cover_problem();
+    let (reduced, reduction) = queens.reduce();
+    assert_eq!((reduced.rows(), reduced.columns()), (queens.rows(), queens.columns()));
+    assert!(reduction.forced_rows().is_empty());
+}
+
+#[test]
+fn check_decomposition() {
+    // Two queens boards side by side, plus an empty row.
+    let (a, b) = (NQueens::new(5).exact_cover_problem(), NQueens::new(6).exact_cover_problem());
+    let primary = a.primary_columns() + b.primary_columns();
+    let column = |j: usize, p: &ExactCoverProblem, before: usize, secondary_before: usize| {
+        match j < p.primary_columns() {
+            true => before + j,
+            false => primary + secondary_before + j - p.primary_columns(),
+        }
+    };
+    let mut rows = vec![];
+    for row in a.ordered_points_rows() {
+        let mut row = row.map(|j| column(j, &a, 0, 0)).collect::<Vec<_>>();
+        row.sort_unstable();
+        rows.push(row);
+    }
+    for row in b.ordered_points_rows() {
+        let mut row = row
+            .map(|j| column(j, &b, a.primary_columns(), a.secondary_columns()))
+            .collect::<Vec<_>>();
+        row.sort_unstable();
+        rows.push(row);
+    }
+    rows.push(vec![]);
+    let spec = ExactCoverProblem::new(rows.iter().map(|r| r.iter().copied()),
+        a.columns() + b.columns(), a.secondary_columns() + b.secondary_columns()).unwrap();
+
+    let decomposition = spec.decompose();
+    assert_eq!(decomposition.components().len(), 3);
+    assert_eq!(decomposition.count_solutions(), Some(10 * 4 * 2));
+    let expected = sort_solutions(ExactCoverSolver::new(&spec).iter_solutions().collect());
+    assert_eq!(sort_solutions(decomposition.iter_solutions().collect()), expected);
+    assert_eq!(decomposition.components()[1].original_row(0), a.rows());
+}
+
+#[test]
+fn check_symmetries() {
+    // The number of solutions to n queens up to symmetry, and in all.
+    assert!(NQueens::new(0).exact_cover_problem()
+        .set_symmetries(NQueens::new(0).symmetries()).is_ok());
+    for (n, canonical, total) in [(1, 1, 1), (3, 0, 0), (4, 1, 2), (5, 2, 10),
+        (6, 1, 4), (7, 6, 40), (8, 12, 92)] {
+        let q = NQueens::new(n);
+        let mut spec = q.exact_cover_problem();
+        spec.set_symmetries(q.symmetries()).unwrap();
+        assert_eq!(spec.has_symmetries(), n > 1);
+        let mut solver = ExactCoverSolver::new(&spec);
+        let solutions = solver.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(solutions.len(), canonical);
+        assert_eq!(solutions.iter().map(|s| solver.orbit_size(s)).sum::<usize>(), total);
+
+        // Parallel solvers find the same classes.
+        let mut parallel = ParallelSolver::new(&spec);
+        parallel.set_threads(3);
+        assert_eq!(parallel.count_solutions(), Some(canonical as u128));
+        let solutions = parallel.iter_solutions().collect::<Vec<_>>();
+        assert_eq!(solutions.iter().map(|s| solver.orbit_size(s)).sum::<usize>(), total);
+    }
+
+    // Assumptions kept by every symmetry leave one solution of each
+    // class, and others are refused.
+    let q = NQueens::new(8);
+    let mut spec = q.exact_cover_problem();
+    spec.set_symmetries(q.symmetries()).unwrap();
+    let mut solver = ExactCoverSolver::new(&spec);
+    solver.reset_with_assumptions(&[], &[0, 7, 56, 63]).unwrap();
+    let solutions = solver.iter_solutions().collect::<Vec<_>>();
+    assert_eq!(solutions.iter().map(|s| solver.orbit_size(s)).sum::<usize>(), 92 - 4*4);
+    assert_eq!(solver.reset_with_assumptions(&[0], &[]),
+        Err(AssumptionError::NotSymmetric));
+    assert_eq!(solver.reset_with_assumptions(&[], &[0, 7, 56]),
+        Err(AssumptionError::NotSymmetric));
+    assert_eq!(solver.iter_solutions().count(), 12);
+
+    // A symmetry swapping two empty rows leaves three classes of
+    // solutions with the one other row, not four.
+    let rows = [vec![0], vec![], vec![]];
+    let mut spec = ExactCoverProblem::new(
+        rows.into_iter().map(|r| r.into_iter()), 1, 0).unwrap();
+    let swap = Symmetry::from_rows(&spec, vec![0, 2, 1]).unwrap();
+    spec.set_symmetries(vec![swap]).unwrap();
+    assert_eq!(ExactCoverSolver::new(&spec).iter_solutions().count(), 3);
+    assert_eq!(count_solutions_parallel(&spec, 2), Some(3));
+
+    // Swapping two squares of the board isn't a symmetry.
+    let q = NQueens::new(4);
+    let mut spec = q.exact_cover_problem();
+    let mut rows = (0..16).collect::<Vec<_>>();
+    rows.swap(0, 1);
+    assert!(Symmetry::from_rows(&spec, rows.clone()).is_none());
+
+    // Nor is swapping rows that give a column different colours, or that
+    // would map a primary column to a secondary one.
+    let coloured = |colour| ExactCoverProblem::new_coloured(
+        [vec![(0, 0), (1, 1)], vec![(0, 0), (1, colour)]].into_iter().map(|r| r.into_iter()),
+        2, 1).unwrap();
+    assert!(Symmetry::from_rows(&coloured(1), vec![1, 0]).is_some());
+    assert!(Symmetry::from_rows(&coloured(2), vec![1, 0]).is_none());
+    let mut optional = ExactCoverProblem::new(
+        [vec![0], vec![1]].into_iter().map(|r| r.into_iter()), 2, 1).unwrap();
+    optional.set_multiplicities(vec![(0, 1)]).unwrap();
+    assert!(Symmetry::from_rows(&optional, vec![1, 0]).is_none());
+    let columns = (0..spec.columns()).collect();
+    let symmetries = vec![q.symmetries()[0].clone(), Symmetry::new(rows, columns)];
+    assert!(matches!(spec.set_symmetries(symmetries),
+        Err(ExactCoverProblemError::InvalidSymmetry { index: 1 })));
+    assert!(!spec.has_symmetries());
+
+    // Costs and multiplicities that break a symmetry set earlier are
+    // refused, while ones it respects are kept.
+    spec.set_symmetries(q.symmetries()).unwrap();
+    let mut costs = vec![0; 16];
+    costs[0] = 1;
+    assert!(matches!(spec.set_costs(costs.clone()),
+        Err(ExactCoverProblemError::InvalidSymmetry { index: 0 })));
+    assert!(!spec.has_costs());
+    costs[3] = 1; costs[12] = 1; costs[15] = 1;
+    spec.set_costs(costs).unwrap();
+    assert!(spec.has_costs());
+    let mut multiplicities = vec![(1, 1); spec.primary_columns()];
+    multiplicities[0] = (0, 1);
+    assert!(matches!(spec.set_multiplicities(multiplicities),
+        Err(ExactCoverProblemError::InvalidSymmetry { .. })));
+    assert!(!spec.has_multiplicities());
+
+    // Every permutation of eight empty rows is too many symmetries.
+    let mut spec = ExactCoverProblem::new(
+        (0..8).map(|_| std::iter::empty()), 0, 0).unwrap();
+    let swap = Symmetry::from_rows(&spec, vec![1, 0, 2, 3, 4, 5, 6, 7]).unwrap();
+    let cycle = Symmetry::from_rows(&spec, vec![1, 2, 3, 4, 5, 6, 7, 0]).unwrap();
+    assert!(matches!(spec.set_symmetries(vec![swap.clone(), cycle]),
+        Err(ExactCoverProblemError::SymmetryGroupTooLarge)));
+    assert!(!spec.has_symmetries());
+    spec.set_symmetries(vec![swap]).unwrap();
+    assert!(spec.has_symmetries());
+}
+
+#[test]
+fn check_assumptions() {
+    // Eight queens with a queen in a corner, then with the corners empty.
+    let spec = NQueens::new(8).exact_cover_problem();
+    let mut solver = ExactCoverSolver::new(&spec);
+    solver.reset_with_assumptions(&[0], &[]).unwrap();
+    let solutions = solver.iter_solutions().collect::<Vec<_>>();
+    assert_eq!(solutions.len(), 4);
+    assert!(solutions.iter().all(|s| s.0.contains(&0)));
+    solver.reset_with_assumptions(&[], &[0, 7, 56, 63]).unwrap();
+    assert_eq!(solver.iter_solutions().count(), 92 - 4*4);
+
+    assert_eq!(solver.reset_with_assumptions(&[0, 1], &[]),
+        Err(AssumptionError::Contradiction { row_idx: 1 }));
+    assert_eq!(solver.reset_with_assumptions(&[5], &[5]),
+        Err(AssumptionError::Contradiction { row_idx: 5 }));
+    assert_eq!(solver.reset_with_assumptions(&[], &[64]),
+        Err(AssumptionError::RowOutOfBounds { row_idx: 64 }));
+    assert!(solver.forbidden_rows().is_empty());
+    assert_eq!(solver.iter_solutions().count(), 92);
+
+    let case = KnuthSimpleWithEmptyRows;
+    let mut solver = ExactCoverSolver::new(&case.spec());
+    let empty = solver.empty_rows()[0];
+    assert_eq!(solver.reset_with_assumptions(&[empty], &[]),
+        Err(AssumptionError::EmptyRow { row_idx: empty }));
+}
+
+#[test]
+fn check_infeasibility_explanation() {
+    // Row 0 is the only row of column 0, leaving column 2 without rows.
+    // Columns 3 and 4 can be covered.
+    let rows: [&[usize]; 5] = [&[0, 1], &[1, 2], &[3], &[4], &[3, 4]];
+    let spec = ExactCoverProblem::new(
+        rows.iter().map(|r| r.iter().copied()), 6, 0).unwrap();
+    let why = spec.explain_infeasibility().unwrap();
+    assert_eq!(why.empty_columns(), [5]);
+    assert_eq!(why.core_columns(), [5]);
+    assert!(why.core_rows().is_empty());
+
+    let spec = ExactCoverProblem::new(
+        rows.iter().map(|r| r.iter().copied()), 5, 0).unwrap();
+    let why = spec.explain_infeasibility().unwrap();
+    assert!(why.empty_columns().is_empty());
+    assert_eq!(why.forced_rows(), [0]);
+    assert_eq!(why.uncoverable_columns(), [2]);
+    assert_eq!(why.core_columns(), [0, 1, 2]);
+    assert_eq!(why.core_rows(), [0, 1]);
+
+    assert!(NQueens::new(4).exact_cover_problem().explain_infeasibility().is_none());
+    let why = NQueens::new(3).exact_cover_problem().explain_infeasibility().unwrap();
+    assert!(why.empty_columns().is_empty());
+    assert!(!why.core_columns().is_empty());
+}
+
+#[test]
+fn check_step_reversal() {
+    // Six queens up to symmetry, then with the corners empty, run to the
+    // end and then back to the start.
+    let q = NQueens::new(6);
+    let mut spec = q.exact_cover_problem();
+    spec.set_symmetries(q.symmetries()).unwrap();
+    let mut solver = ExactCoverSolver::new(&spec);
+    assert_eq!(solver.prev_step(), None);
+    solver.set_record_steps(true);
+    let steps = solver.iter_steps().collect::<Vec<_>>();
+    let mut undone = std::iter::from_fn(|| solver.prev_step()).collect::<Vec<_>>();
+    undone.reverse();
+    assert_eq!(undone, steps);
+    assert_eq!(solver.iter_steps().collect::<Vec<_>>(), steps);
+
+    solver.reset_with_assumptions(&[], &[0, 5, 30, 35]).unwrap();
+    assert_eq!(solver.prev_step(), None);
+    let start = solver.current_partial_solution();
+    let steps = solver.iter_steps().collect::<Vec<_>>();
+    while solver.prev_step().is_some() {}
+    assert_eq!(solver.current_partial_solution(), start);
+    assert_eq!(solver.iter_steps().collect::<Vec<_>>(), steps);
+
+    // Turning the record off forgets it.
+    solver.reset_with_assumptions(&[], &[]).unwrap();
+    solver.next_step();
+    solver.set_record_steps(false);
+    assert_eq!(solver.prev_step(), None);
+
+    // Passing over solutions with empty rows that aren't canonical.
+    let rows = [vec![0], vec![], vec![], vec![0]];
+    let mut spec = ExactCoverProblem::new(
+        rows.into_iter().map(|r| r.into_iter()), 1, 0).unwrap();
+    let swap = Symmetry::from_rows(&spec, vec![0, 2, 1, 3]).unwrap();
+    spec.set_symmetries(vec![swap]).unwrap();
+    let mut solver = ExactCoverSolver::new(&spec);
+    solver.set_record_steps(true);
+    let steps = solver.iter_steps().collect::<Vec<_>>();
+    let mut undone = std::iter::from_fn(|| solver.prev_step()).collect::<Vec<_>>();
+    undone.reverse();
+    assert_eq!(undone, steps);
+    assert_eq!(solver.iter_steps().collect::<Vec<_>>(), steps);
+}
+
+#[test]
+fn check_solutions_with_other_choosers() {
+    for case in [&KnuthSimple as &dyn TestCase, &KnuthSimpleWithEmptyRows,
+        &MixedOptionalAndEmptyRows, &ZeroRowsThreeCols] {
+        assert_solution_match_with(case, FirstColumn);
+        assert_solution_match_with(case, MrvRandomTies::new(7));
+        assert_solution_match_with(case, Priority::new(vec![0, 3, 0, 5]));
+    }
+}
+
+#[test]
+fn check_chooser_column_order() {
+    let first_col = |chooser| {
+        let spec = KnuthSimple.spec();
+        let mut solver = ExactCoverSolver::with_chooser(&spec, chooser);
+        match solver.next_step() {
+            Some(SolverStep::SelectColumn { col, .. }) => col,
+            s => panic!("unexpected step {s:?}"),
+        }
+    };
+    // Columns 0, 1, 2, 4 and 5 all have two rows; 3 and 6 have three.
+    assert_eq!(first_col(Priority::new(vec![])), 0);
+    assert_eq!(first_col(Priority::new(vec![0, 0, 0, 1, 0, 0, 1])), 3);
+    assert_eq!(first_col(Priority::new(vec![0, 0, 0, 0, 0, 0, 0, -1, 1])), 0);
+    assert_eq!(
+        ExactCoverSolver::with_chooser(&KnuthSimple.spec(), FirstColumn)
+            .iter_steps()
+            .find_map(|s| match s {
+                SolverStep::SelectColumn { col, size, .. } => Some((col, size)),
+                _ => None,
+            }),
+        Some((0, 2)),
+    );
+}
+
+#[test]
+fn check_buckets_steps_identical_to_mrv() {
+    let mut specs = vec![
+        KnuthSimple.spec(), KnuthSimpleWithEmptyRows.spec(),
+        AllSecondaryRows.spec(), MixedOptionalAndEmptyRows.spec(),
+    ];
+    specs.extend((0..8).map(|n| NQueens::new(n).exact_cover_problem()));
+    // Domino tilings of odd rings, whose ties span many words of the
+    // buckets' bitsets.
+    for width in [129, 4161] {
+        let rows = (0..width).map(|i| match i + 1 == width {
+            true => vec![0, i],
+            false => vec![i, i + 1],
+        });
+        specs.push(ExactCoverProblem::new(
+            rows.map(|row| row.into_iter()), width, 0).unwrap());
+    }
+    for spec in &specs {
+        let mrv = ExactCoverSolver::new(spec).iter_steps().collect::<Vec<_>>();
+        let buckets = ExactCoverSolver::with_chooser(spec, MrvBuckets::new())
+            .iter_steps()
+            .collect::<Vec<_>>();
+        assert_eq!(mrv, buckets);
+    }
+}
+
+#[test]
+fn check_randomised_solutions() {
+    let cases: [&dyn TestCase; 4] = [
+        &KnuthSimple, &KnuthSimpleWithEmptyRows,
+        &AllSecondaryRows, &MixedOptionalAndEmptyRows,
+    ];
+    for case in cases {
+        let spec = case.spec();
+        for seed in 0..10 {
+            let actual = ExactCoverSolver::new_randomised(&spec, seed)
+                .iter_solutions()
+                .collect::<Vec<_>>();
+            assert_eq!(sort_solutions(case.expected_solutions()), sort_solutions(actual));
+        }
+    }
+
+    // n-queens has 92 solutions for n = 8.
+    let spec = NQueens::new(8).exact_cover_problem();
+    for seed in 0..10 {
+        let actual = ExactCoverSolver::new_randomised(&spec, seed)
+            .iter_solutions()
+            .collect::<Vec<_>>();
+        assert_eq!(actual.len(), 92);
+        assert_eq!(
+            sort_solutions(actual),
+            sort_solutions(ExactCoverSolver::new(&spec).iter_solutions().collect()),
+        );
+    }
+}
+
+#[test]
+fn check_randomised_replay() {
+    let spec = NQueens::new(8).exact_cover_problem();
+    let steps = |seed| ExactCoverSolver::new_randomised(&spec, seed)
+        .iter_steps()
+        .collect::<Vec<_>>();
+    assert_eq!(steps(42), steps(42));
+    assert_ne!(steps(42), steps(43));
+}
+
+#[test]
+fn check_randomised_restarts() {
+    let spec = NQueens::new(8).exact_cover_problem();
+    let expected = sort_solutions(ExactCoverSolver::new(&spec).iter_solutions().collect());
+    let mut solver = ExactCoverSolver::new_randomised(&spec, 7);
+    solver.set_restart_after(Some(20));
+    let mut restarts = 0;
+    let mut found = 0;
+    for step in solver.iter_steps().take(20_000) {
+        match step {
+            SolverStep::Restart => restarts += 1,
+            SolverStep::ReportSolution(s) => {
+                assert!(expected.contains(&sort_solutions(vec![s]).remove(0)));
+                found += 1;
+            },
+            _ => (),
+        }
+    }
+    assert!(restarts > 0);
+    assert!(found > 0);
+
+    // After a restart the solver is back at the root with nothing chosen.
+    let mut solver = ExactCoverSolver::new_randomised(&spec, 7);
+    solver.set_restart_after(Some(5));
+    while let Some(step) = solver.next_step() {
+        if step == SolverStep::Restart {
+            assert!(solver.current_partial_solution().0.is_empty());
+            break;
+        }
+    }
+}
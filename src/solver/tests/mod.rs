@@ -1,5 +1,6 @@
 #![cfg(test)]
 
+mod brute_force;
 mod cases;
 mod invariants;
 
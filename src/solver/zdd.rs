@@ -0,0 +1,202 @@
+//! Zero-suppressed decision diagrams of solution families.
+//!
+//! Following Nishino et al.'s DXZ, the solver builds the diagram with
+//! the same search as Algorithm X, but memoises the diagram of every
+//! subproblem on the state of the columns it reaches, so a subproblem
+//! reached by different paths is only solved once. Every node stands for
+//! the family of solutions of some subproblem: those not containing its
+//! row (its `lo` child) together with those containing it (its `hi`
+//! child, plus the row). The whole diagram can be far smaller than the
+//! number of solutions.
+
+use std::collections::HashMap;
+
+use super::output::ExactCover;
+use super::rng::SplitMix64;
+
+/// The empty family.
+const BOTTOM: usize = 0;
+/// The family containing only the empty set.
+const TOP: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ZddNode {
+    row: usize,
+    lo: usize,
+    hi: usize,
+}
+
+/// A zero-suppressed decision diagram (ZDD) of every solution of a
+/// problem. This `struct` is created by the
+/// [`solution_zdd`](super::ExactCoverSolver::solution_zdd) method on
+/// [`ExactCoverSolver`](super::ExactCoverSolver).
+///
+/// It can count the solutions exactly, sample them uniformly at random
+/// and enumerate them lazily, without ever listing them all.
+#[derive(Debug, Clone)]
+pub struct SolutionZdd {
+    // Indices 0 and 1 hold the terminals, whose fields are unused.
+    nodes: Vec<ZddNode>,
+    // The number of solutions below each node, or `None` if it doesn't
+    // fit in a u128.
+    counts: Vec<Option<u128>>,
+    root: usize,
+}
+
+impl SolutionZdd {
+    /// The number of solutions, or `None` if it doesn't fit in a `u128`.
+    pub fn count(&self) -> Option<u128> {
+        self.counts[self.root]
+    }
+
+    /// The number of nodes in the diagram, not counting the terminals.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len() - 2
+    }
+
+    /// Returns an iterator through every solution, in no particular
+    /// order.
+    pub fn iter(&self) -> ZddSolutions<'_> {
+        ZddSolutions {
+            zdd: self,
+            stack: vec![(self.root, 0)],
+            path: vec![],
+        }
+    }
+
+    /// Returns an endless iterator of solutions chosen independently and
+    /// uniformly at random, determined entirely by the seed. It is empty
+    /// if there are no solutions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of solutions doesn't fit in a `u128`.
+    pub fn samples(&self, seed: u64) -> ZddSamples<'_> {
+        assert!(self.count().is_some(), "too many solutions to sample");
+        ZddSamples { zdd: self, rng: SplitMix64::new(seed) }
+    }
+
+    fn count_of(&self, node: usize) -> u128 {
+        self.counts[node].unwrap()
+    }
+}
+
+/// Builds a [`SolutionZdd`] bottom up, sharing identical nodes and
+/// memoising the diagrams of subproblems.
+#[derive(Debug)]
+pub(crate) struct ZddBuilder {
+    nodes: Vec<ZddNode>,
+    unique: HashMap<ZddNode, usize>,
+    memo: HashMap<Vec<u64>, usize>,
+}
+
+impl ZddBuilder {
+    pub(crate) fn new() -> Self {
+        let terminal = ZddNode { row: usize::MAX, lo: BOTTOM, hi: BOTTOM };
+        Self {
+            nodes: vec![terminal, terminal],
+            unique: HashMap::new(),
+            memo: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn bottom(&self) -> usize { BOTTOM }
+    pub(crate) fn top(&self) -> usize { TOP }
+
+    /// The family of `lo` together with `row` added to each set of `hi`.
+    pub(crate) fn node(&mut self, row: usize, lo: usize, hi: usize) -> usize {
+        if hi == BOTTOM {
+            return lo;
+        }
+        let node = ZddNode { row, lo, hi };
+        let next = self.nodes.len();
+        *self.unique.entry(node).or_insert_with(|| {
+            self.nodes.push(node);
+            next
+        })
+    }
+
+    /// The memoised diagram for a subproblem, if there is one.
+    pub(crate) fn lookup(&self, key: &[u64]) -> Option<usize> {
+        self.memo.get(key).copied()
+    }
+
+    pub(crate) fn memoise(&mut self, key: Vec<u64>, node: usize) {
+        self.memo.insert(key, node);
+    }
+
+    pub(crate) fn finish(self, root: usize) -> SolutionZdd {
+        // Children always come before their parents.
+        let mut counts = vec![Some(0), Some(1)];
+        for node in &self.nodes[2..] {
+            let count = counts[node.lo]
+                .zip(counts[node.hi])
+                .and_then(|(lo, hi): (u128, u128)| lo.checked_add(hi));
+            counts.push(count);
+        }
+        SolutionZdd { nodes: self.nodes, counts, root }
+    }
+}
+
+/// An iterator through the solutions in a [`SolutionZdd`]. This `struct`
+/// is created by the [`iter`](SolutionZdd::iter) method on
+/// [`SolutionZdd`].
+pub struct ZddSolutions<'a> {
+    zdd: &'a SolutionZdd,
+    // Nodes still to visit, each with the length of the prefix of
+    // `path` leading to it.
+    stack: Vec<(usize, usize)>,
+    path: Vec<usize>,
+}
+
+impl<'a> Iterator for ZddSolutions<'a> {
+    type Item = ExactCover;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, len)) = self.stack.pop() {
+            self.path.truncate(len);
+            match node {
+                BOTTOM => (),
+                TOP => return Some(ExactCover(self.path.clone())),
+                _ => {
+                    let ZddNode { row, lo, hi } = self.zdd.nodes[node];
+                    self.stack.push((lo, len));
+                    self.stack.push((hi, len + 1));
+                    self.path.push(row);
+                },
+            }
+        }
+        None
+    }
+}
+
+/// An endless iterator of uniformly random solutions from a
+/// [`SolutionZdd`]. This `struct` is created by the
+/// [`samples`](SolutionZdd::samples) method on [`SolutionZdd`].
+pub struct ZddSamples<'a> {
+    zdd: &'a SolutionZdd,
+    rng: SplitMix64,
+}
+
+impl<'a> Iterator for ZddSamples<'a> {
+    type Item = ExactCover;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut node = self.zdd.root;
+        if node == BOTTOM {
+            return None;
+        }
+        let mut rows = vec![];
+        while node != TOP {
+            let ZddNode { row, lo, hi } = self.zdd.nodes[node];
+            let total = self.zdd.count_of(node);
+            if self.rng.below_u128(total) < self.zdd.count_of(hi) {
+                rows.push(row);
+                node = hi;
+            } else {
+                node = lo;
+            }
+        }
+        Some(ExactCover(rows))
+    }
+}